@@ -0,0 +1,318 @@
+use crate::bank_file;
+use crate::{AppError, AppResult};
+use log::info;
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory backups go to next to each bank, unless a directory is set.
+pub const DEFAULT_BACKUP_DIR: &str = ".backups";
+/// Backups kept per bank unless set otherwise.
+pub const DEFAULT_KEEP: usize = 20;
+const BACKUP_EXTENSION: &str = "bak";
+/// Ends the file stem of the backups `restore` makes of the content it replaces.
+const BEFORE_RESTORE_SUFFIX: &str = "-before-restore";
+/// UTC, in the ISO 8601 basic format so it sorts, and is a valid file name, everywhere.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.6fZ";
+
+/// Where the previous content of a bank is kept before it is overwritten.
+///
+/// Each bank has its own backup directory holding one `<timestamp>.bak` file per
+/// backup. By default that is `.backups/<file name>` next to the bank. With `dir` set,
+/// it is `<dir>/<file name>-<path hash>` instead, the hash of the bank's full path
+/// keeping apart banks of the same name from different authors or players.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupPolicy {
+    pub dir: Option<PathBuf>,
+    /// Backups kept per bank; older ones are removed. `0` turns backups off.
+    pub keep: usize,
+}
+
+/// One backup of a bank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    /// When the backup was made, e.g. `20261017T120344.123456Z`.
+    pub timestamp: String,
+    pub path: PathBuf,
+    /// Whether `restore` made the backup of the content it replaced.
+    pub before_restore: bool,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy { dir: None, keep: DEFAULT_KEEP }
+    }
+}
+
+impl BackupPolicy {
+    pub fn disabled() -> Self {
+        BackupPolicy { dir: None, keep: 0 }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.keep > 0
+    }
+
+    /// The directory holding the backups of `bank`.
+    pub fn backup_dir(&self, bank: &Path) -> PathBuf {
+        let bank = bank.canonicalize().unwrap_or_else(|_| bank.to_path_buf());
+        let file_name = bank.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        match &self.dir {
+            Some(dir) => {
+                let hash = hex::encode(Sha1::digest(bank.to_string_lossy().as_bytes()));
+                dir.join(format!("{}-{}", file_name, &hash[..8]))
+            }
+            None => bank.parent().unwrap_or(Path::new("")).join(DEFAULT_BACKUP_DIR).join(file_name),
+        }
+    }
+
+    /// Backs up the current content of `bank` and removes backups beyond `keep`.
+    ///
+    /// Nothing is backed up when backups are off, the bank does not exist yet, or its
+    /// content is the same as in the latest backup.
+    pub fn backup(&self, bank: &Path) -> AppResult<Option<Backup>> {
+        self.make_backup(bank, false)
+    }
+
+    fn make_backup(&self, bank: &Path, before_restore: bool) -> AppResult<Option<Backup>> {
+        if !self.is_enabled() || !bank.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read(bank)?;
+        let mut backups = self.list(bank)?;
+        if let Some(latest) = backups.last()
+            && fs::read(&latest.path).is_ok_and(|previous| previous == content)
+        {
+            return Ok(None);
+        }
+
+        let dir = self.backup_dir(bank);
+        fs::create_dir_all(&dir)?;
+        let timestamp = jiff::Timestamp::now().strftime(TIMESTAMP_FORMAT).to_string();
+        let suffix = if before_restore { BEFORE_RESTORE_SUFFIX } else { "" };
+        let backup = Backup {
+            path: dir.join(format!("{}{}.{}", timestamp, suffix, BACKUP_EXTENSION)),
+            timestamp,
+            before_restore,
+        };
+        fs::write(&backup.path, &content)?;
+        info!("Backed up {} to {}", bank.display(), backup.path.display());
+
+        backups.push(backup.clone());
+        let excess = backups.len().saturating_sub(self.keep);
+        for old in &backups[..excess] {
+            fs::remove_file(&old.path)?;
+        }
+        Ok(Some(backup))
+    }
+
+    /// The backups of `bank`, oldest first.
+    pub fn list(&self, bank: &Path) -> AppResult<Vec<Backup>> {
+        let dir = self.backup_dir(bank);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(BACKUP_EXTENSION) {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                let timestamp = stem.strip_suffix(BEFORE_RESTORE_SUFFIX);
+                backups.push(Backup {
+                    timestamp: timestamp.unwrap_or(stem).to_string(),
+                    path: path.clone(),
+                    before_restore: timestamp.is_some(),
+                });
+            }
+        }
+        backups.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(backups)
+    }
+
+    /// Puts a backup of `bank` back in its place, the latest one or the latest taken at
+    /// or before `at`. The content being replaced is backed up first, as
+    /// `<timestamp>-before-restore.bak`, so a restore can itself be undone with `at`.
+    /// Without `at` those backups are passed over, so restoring again keeps the same
+    /// content rather than undoing the restore.
+    ///
+    /// `at` is a timestamp as listed, or the start of one: `20261017T12` is the latest
+    /// backup taken in that hour. Dashes and colons are ignored, so `2026-10-17T12:03`
+    /// works as well.
+    pub fn restore(&self, bank: &Path, at: Option<&str>) -> AppResult<Backup> {
+        let backups = self.list(bank)?;
+        let backup = match at {
+            None => backups.iter().rev().find(|backup| !backup.before_restore),
+            Some(at) => {
+                let at: String = at.chars().filter(|c| !matches!(c, '-' | ':')).collect();
+                backups
+                    .iter()
+                    .rev()
+                    .find(|backup| backup.timestamp <= at || backup.timestamp.starts_with(&at))
+            }
+        }
+        .cloned()
+        .ok_or_else(|| AppError::NoBackup(bank.to_path_buf()))?;
+
+        let content = fs::read(&backup.path)?;
+        let current = if bank.is_file() { Some(fs::read(bank)?) } else { None };
+        if current.as_ref() == Some(&content) {
+            return Ok(backup);
+        }
+        self.make_backup(bank, true)?;
+        let expected_hash = current.as_deref().map(bank_file::content_hash);
+        bank_file::write(bank, &content, expected_hash.as_deref(), &BackupPolicy::disabled())?;
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory holding a bank, removed when dropped.
+    struct Scratch {
+        dir: PathBuf,
+        bank: PathBuf,
+    }
+
+    impl Scratch {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("sc2_bank_signer-backup-{}-{}", std::process::id(), test));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let bank = dir.join("Bank.SC2Bank");
+            Scratch { dir, bank }
+        }
+
+        fn write(&self, content: &str) {
+            fs::write(&self.bank, content).unwrap();
+        }
+
+        fn content(&self) -> String {
+            fs::read_to_string(&self.bank).unwrap()
+        }
+
+        /// Adds a backup holding `content` as if it had been taken at `timestamp`.
+        fn add_backup(&self, policy: &BackupPolicy, timestamp: &str, content: &str) {
+            let dir = policy.backup_dir(&self.bank);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("{}.{}", timestamp, BACKUP_EXTENSION)), content).unwrap();
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn contents(backups: &[Backup]) -> Vec<String> {
+        backups.iter().map(|backup| fs::read_to_string(&backup.path).unwrap()).collect()
+    }
+
+    #[test]
+    fn keeps_the_latest_backups() {
+        let scratch = Scratch::new("keep");
+        let policy = BackupPolicy { dir: None, keep: 2 };
+        for content in ["1", "2", "3"] {
+            scratch.write(content);
+            assert!(policy.backup(&scratch.bank).unwrap().is_some());
+        }
+        let backups = policy.list(&scratch.bank).unwrap();
+        assert_eq!(contents(&backups), ["2", "3"]);
+        assert_eq!(backups[0].path.parent(), Some(scratch.dir.join(".backups/Bank.SC2Bank").as_path()));
+        assert!(backups[0].timestamp < backups[1].timestamp);
+    }
+
+    #[test]
+    fn skips_a_backup_identical_to_the_latest() {
+        let scratch = Scratch::new("identical");
+        let policy = BackupPolicy::default();
+        scratch.write("1");
+        assert!(policy.backup(&scratch.bank).unwrap().is_some());
+        assert_eq!(policy.backup(&scratch.bank).unwrap(), None);
+        scratch.write("2");
+        assert!(policy.backup(&scratch.bank).unwrap().is_some());
+        assert_eq!(contents(&policy.list(&scratch.bank).unwrap()), ["1", "2"]);
+    }
+
+    #[test]
+    fn backs_up_nothing_when_disabled_or_missing() {
+        let scratch = Scratch::new("disabled");
+        assert_eq!(BackupPolicy::default().backup(&scratch.bank).unwrap(), None);
+        scratch.write("1");
+        assert_eq!(BackupPolicy::disabled().backup(&scratch.bank).unwrap(), None);
+        assert_eq!(BackupPolicy::default().list(&scratch.bank).unwrap(), []);
+    }
+
+    #[test]
+    fn keeps_backups_in_the_backup_dir() {
+        let scratch = Scratch::new("backup-dir");
+        let policy = BackupPolicy { dir: Some(scratch.dir.join("all")), keep: DEFAULT_KEEP };
+        scratch.write("1");
+        let backup = policy.backup(&scratch.bank).unwrap().unwrap();
+        let dir = backup.path.parent().unwrap();
+        assert_eq!(dir.parent(), Some(scratch.dir.join("all").as_path()));
+        let name = dir.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("Bank.SC2Bank-") && name.len() == "Bank.SC2Bank-".len() + 8, "{}", name);
+        assert!(!scratch.dir.join(DEFAULT_BACKUP_DIR).exists());
+
+        // Banks of the same name elsewhere get their own directory.
+        let other = scratch.dir.join("other/Bank.SC2Bank");
+        fs::create_dir_all(other.parent().unwrap()).unwrap();
+        assert_ne!(policy.backup_dir(&other), dir);
+    }
+
+    #[test]
+    fn restores_the_latest_at_or_before_a_time() {
+        let scratch = Scratch::new("at");
+        let policy = BackupPolicy::default();
+        scratch.add_backup(&policy, "20201017T110000.000000Z", "11:00");
+        scratch.add_backup(&policy, "20201017T120300.000000Z", "12:03");
+        scratch.add_backup(&policy, "20201017T125900.000000Z", "12:59");
+        scratch.add_backup(&policy, "20201018T080000.000000Z", "next day");
+        scratch.write("current");
+
+        let restore = |at: &str| policy.restore(&scratch.bank, Some(at)).unwrap().timestamp;
+        // A prefix matches the last backup it starts.
+        assert_eq!(restore("20201017T12"), "20201017T125900.000000Z");
+        assert_eq!(restore("2020-10-17T12:03"), "20201017T120300.000000Z");
+        // Otherwise the last backup at or before the time.
+        assert_eq!(restore("20201017T1230"), "20201017T120300.000000Z");
+        assert_eq!(restore("20201017T235959"), "20201017T125900.000000Z");
+        assert_eq!(restore("20201017T110000.000000Z"), "20201017T110000.000000Z");
+        assert_eq!(scratch.content(), "11:00");
+        assert!(matches!(policy.restore(&scratch.bank, Some("20201016")), Err(AppError::NoBackup(_))));
+    }
+
+    #[test]
+    fn restoring_again_keeps_the_restored_content() {
+        let scratch = Scratch::new("restore");
+        let policy = BackupPolicy::default();
+        scratch.add_backup(&policy, "20201017T110000.000000Z", "backed up");
+        scratch.write("current");
+
+        assert_eq!(policy.restore(&scratch.bank, None).unwrap().timestamp, "20201017T110000.000000Z");
+        assert_eq!(scratch.content(), "backed up");
+        assert_eq!(policy.restore(&scratch.bank, None).unwrap().timestamp, "20201017T110000.000000Z");
+        assert_eq!(scratch.content(), "backed up");
+
+        // The content the restore replaced is kept, and can be restored by its time.
+        let backups = policy.list(&scratch.bank).unwrap();
+        assert_eq!(contents(&backups), ["backed up", "current"]);
+        assert!(!backups[0].before_restore && backups[1].before_restore);
+        assert!(backups[1].path.to_str().unwrap().ends_with("-before-restore.bak"));
+        policy.restore(&scratch.bank, Some(&backups[1].timestamp)).unwrap();
+        assert_eq!(scratch.content(), "current");
+    }
+
+    #[test]
+    fn restores_nothing_without_backups() {
+        let scratch = Scratch::new("none");
+        scratch.write("current");
+        assert!(matches!(BackupPolicy::default().restore(&scratch.bank, None), Err(AppError::NoBackup(_))));
+        assert_eq!(scratch.content(), "current");
+    }
+}
@@ -0,0 +1,268 @@
+use crate::backup::BackupPolicy;
+use crate::{AppError, AppResult};
+use sha1::{Digest, Sha1};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The SHA-1 of file content, in hex, to tell whether a file changed since it was read.
+pub fn content_hash(content: &[u8]) -> String {
+    hex::encode(Sha1::digest(content))
+}
+
+/// Replaces the file at `path` with `content`, so that it never holds anything but
+/// either the old or the complete new content.
+///
+/// The content goes to a temporary file next to `path`, is synced to disk, and then
+/// renamed over `path`. While the file is replaced an advisory lock is held on the
+/// `.<file name>.lock` file next to it, so two runs of the tool do not write the same
+/// bank at once. The lock is not on the file itself, which the rename replaces, and the
+/// lock file is left in place, as removing it would let two runs lock different files
+/// of the same name. If `expected_hash` is
+/// given, the file must still have that `content_hash`, i.e. be unchanged since it was
+/// read; otherwise nothing is written and `AppError::ChangedOnDisk` is returned. The
+/// old content is backed up according to `backup` before it is replaced.
+pub fn write(path: &Path, content: &[u8], expected_hash: Option<&str>, backup: &BackupPolicy) -> AppResult<()> {
+    write_all(&[FileWrite { path, content, expected_hash, backup }])
+}
+
+/// One file for `write_all` to replace, with the arguments of `write`.
+#[derive(Debug, Clone, Copy)]
+pub struct FileWrite<'a> {
+    pub path: &'a Path,
+    pub content: &'a [u8],
+    pub expected_hash: Option<&'a str>,
+    pub backup: &'a BackupPolicy,
+}
+
+/// Replaces several files like `write`, all of them or none.
+///
+/// Every file is locked, checked against its expected hash and backed up before any is
+/// written, so a changed file fails the whole write. Should writing one file fail after others
+/// were replaced, those are put back to their old content, which is held in memory,
+/// and the error is returned. Only if putting one back fails too is it left with its
+/// new content, which is logged.
+pub fn write_all(files: &[FileWrite]) -> AppResult<()> {
+    // A second lock of a file the process already holds locked would wait forever.
+    let mut canonical = Vec::with_capacity(files.len());
+    for file in files {
+        let path = fs::canonicalize(file.path).unwrap_or_else(|_| file.path.to_path_buf());
+        if canonical.contains(&path) {
+            return Err(AppError::DuplicateBank(file.path.to_path_buf()));
+        }
+        canonical.push(path);
+    }
+
+    // Held until the new content is in place. Taken in path order, so two runs locking
+    // the same banks do not wait for each other.
+    let mut order: Vec<usize> = (0..files.len()).collect();
+    order.sort_by(|&a, &b| canonical[a].cmp(&canonical[b]));
+    let mut _locks = Vec::with_capacity(files.len());
+    for &index in &order {
+        let lock_path = sidecar_path(files[index].path, "lock");
+        match OpenOptions::new().write(true).create(true).truncate(false).open(&lock_path) {
+            Ok(file) => {
+                file.lock()?;
+                _locks.push(file);
+            }
+            // The directory does not exist, so neither does a file to write over.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    // The content each file has now, `None` if it does not exist.
+    let mut originals = Vec::with_capacity(files.len());
+    for file in files {
+        let current = match fs::read(file.path) {
+            Ok(current) => Some(current),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        if let Some(expected_hash) = file.expected_hash
+            && current.as_deref().map(content_hash).as_deref() != Some(expected_hash)
+        {
+            return Err(AppError::ChangedOnDisk(file.path.to_path_buf()));
+        }
+        originals.push(current);
+    }
+    for file in files {
+        file.backup.backup(file.path)?;
+    }
+
+    for (index, file) in files.iter().enumerate() {
+        if let Err(err) = replace(file.path, file.content) {
+            for (written, original) in files[..index].iter().zip(&originals) {
+                let restored = match original {
+                    Some(original) => replace(written.path, original),
+                    None => fs::remove_file(written.path).map_err(AppError::from),
+                };
+                match restored {
+                    Ok(()) => log::warn!("Put {} back to its content before the write", written.path.display()),
+                    Err(restore_err) => log::error!(
+                        "{} was written but could not be put back: {}",
+                        written.path.display(),
+                        restore_err
+                    ),
+                }
+            }
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Writes `content` to a temporary file and renames it over `path`. Called under the
+/// lock of `path`, so a temporary file already there was left behind by an earlier run
+/// that did not finish, and is removed.
+fn replace(path: &Path, content: &[u8]) -> AppResult<()> {
+    let temp_path = temp_path(path);
+    match fs::remove_file(&temp_path) {
+        Ok(()) => log::warn!("Removed {}, left behind by an earlier write", temp_path.display()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    let result = write_temp(&temp_path, path, content).and_then(|()| fs::rename(&temp_path, path));
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+    sync_dir(path);
+    Ok(())
+}
+
+/// `.<file name>.tmp` in the same directory, so the rename stays on one file system.
+fn temp_path(path: &Path) -> PathBuf {
+    sidecar_path(path, "tmp")
+}
+
+/// The hidden file `.<file name>.<extension>` next to `path`.
+fn sidecar_path(path: &Path, extension: &str) -> PathBuf {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", file_name, extension))
+}
+
+fn write_temp(temp_path: &Path, path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp_path)?;
+    file.write_all(content)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+/// Syncs the directory of `path`, so the rename itself survives a crash. Only possible,
+/// and only needed, on Unix.
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty())
+        && let Ok(dir) = File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory holding the files `names` with content `old`, removed when dropped.
+    struct Files {
+        dir: PathBuf,
+        paths: Vec<PathBuf>,
+    }
+
+    impl Files {
+        fn new(test: &str, names: &[&str]) -> Self {
+            let dir = std::env::temp_dir().join(format!("sc2_bank_signer-bank_file-{}-{}", std::process::id(), test));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let paths: Vec<PathBuf> = names.iter().map(|name| dir.join(name)).collect();
+            for path in &paths {
+                fs::write(path, "old").unwrap();
+            }
+            Files { dir, paths }
+        }
+
+        fn write(&self, expected_hash: Option<&str>) -> AppResult<()> {
+            let backup = BackupPolicy::disabled();
+            let files: Vec<FileWrite> = self
+                .paths
+                .iter()
+                .map(|path| FileWrite { path, content: b"new", expected_hash, backup: &backup })
+                .collect();
+            write_all(&files)
+        }
+
+        fn contents(&self) -> Vec<String> {
+            self.paths.iter().map(|path| fs::read_to_string(path).unwrap()).collect()
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn writes_every_file() {
+        let files = Files::new("every", &["A.SC2Bank", "B.SC2Bank"]);
+        files.write(Some(&content_hash(b"old"))).unwrap();
+        assert_eq!(files.contents(), ["new", "new"]);
+    }
+
+    #[test]
+    fn writes_nothing_when_a_file_changed() {
+        let files = Files::new("changed", &["A.SC2Bank", "B.SC2Bank"]);
+        fs::write(&files.paths[1], "changed").unwrap();
+        let err = files.write(Some(&content_hash(b"old"))).unwrap_err();
+        assert!(matches!(err, AppError::ChangedOnDisk(path) if path == files.paths[1]));
+        assert_eq!(files.contents(), ["old", "changed"]);
+    }
+
+    #[test]
+    fn puts_written_files_back_when_a_write_fails() {
+        let files = Files::new("rollback", &["A.SC2Bank", "B.SC2Bank", "C.SC2Bank"]);
+        // A directory, so the temporary file for the second bank cannot be created.
+        fs::create_dir(temp_path(&files.paths[1])).unwrap();
+        assert!(files.write(None).is_err());
+        assert_eq!(files.contents(), ["old", "old", "old"]);
+    }
+
+    #[test]
+    fn removes_a_temporary_file_left_behind() {
+        let files = Files::new("stale", &["A.SC2Bank"]);
+        fs::write(temp_path(&files.paths[0]), "stale").unwrap();
+        files.write(None).unwrap();
+        assert_eq!(files.contents(), ["new"]);
+        assert!(!temp_path(&files.paths[0]).exists());
+    }
+
+    #[test]
+    fn locks_a_file_that_the_write_does_not_replace() {
+        let files = Files::new("lock", &["A.SC2Bank"]);
+        files.write(None).unwrap();
+        let lock = File::open(sidecar_path(&files.paths[0], "lock")).unwrap();
+        // Held by another run, the lock makes this one wait.
+        lock.try_lock().unwrap();
+        let path = files.paths[0].clone();
+        let writer = std::thread::spawn(move || write(&path, b"newer", None, &BackupPolicy::disabled()));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!writer.is_finished());
+        assert_eq!(files.contents(), ["new"]);
+        lock.unlock().unwrap();
+        writer.join().unwrap().unwrap();
+        assert_eq!(files.contents(), ["newer"]);
+    }
+
+    #[test]
+    fn rejects_a_file_given_twice() {
+        let files = Files::new("twice", &["A.SC2Bank", "A.SC2Bank"]);
+        let err = files.write(None).unwrap_err();
+        assert!(matches!(err, AppError::DuplicateBank(_)));
+        assert_eq!(files.contents(), ["old", "old"]);
+    }
+}
@@ -1,250 +1,495 @@
-use crate::bank_parser::attribute::Attribute;
-use crate::bank_parser::key::Key;
+use crate::backup::BackupPolicy;
+use crate::bank_file::{self, content_hash};
+use crate::bank_parser::collector::SectionCollector;
+use crate::bank_parser::diagnostic::{Diagnostic, Span};
+use crate::bank_parser::document::{BankDocument, DocumentBuilder, Element, Node};
 use crate::bank_parser::section::Section;
-use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::signature::{BankSignature, Payload, PayloadHasher, VerificationReport};
 use crate::bank_path::BankPath;
-use crate::{AppError, AppResult, Args};
-use regex::Regex;
-use sha1::{Digest, Sha1};
+use crate::signing_context::SigningContext;
+use crate::{AppError, AppResult, BankArgs};
+use std::fmt;
 use std::fs;
-use xml::reader::XmlEvent;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use xml::common::Position;
+use xml::reader::{ParserConfig, XmlEvent};
 use xml::EventReader;
 
 pub mod attribute;
+mod collector;
+pub mod diagnostic;
+pub mod diff;
+pub mod document;
+mod edit;
 pub mod fixed;
 pub mod flag;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod key;
+pub mod key_path;
+pub mod merge;
+#[cfg(feature = "serde")]
+pub mod patch;
+#[cfg(feature = "serde")]
+pub mod schema;
 pub mod section;
+pub mod signature;
+pub mod source_map;
 pub mod value_element;
 
+// Tag and attribute names of the bank format.
+pub(crate) const SECTION_TAG: &str = "Section";
+pub(crate) const KEY_TAG: &str = "Key";
+pub(crate) const VALUE_TAG: &str = "Value";
+pub(crate) const SIGNATURE_TAG: &str = "Signature";
+pub(crate) const NAME_ATTRIBUTE: &str = "name";
+
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum BankParserError {
     #[error("Section tag missing 'name' attribute")]
-    SectionTagMissingName,
+    SectionTagMissingName(Option<Span>),
     #[error("Key tag missing 'name' attribute")]
-    KeyTagMissingName,
+    KeyTagMissingName(Option<Span>),
+    #[error("Bank has no root element")]
+    MissingRootElement,
+    #[error("Section '{0}' not found")]
+    SectionNotFound(String),
+    #[error("Key '{section}/{key}' not found")]
+    KeyNotFound { section: String, key: String },
+    #[error("Section '{0}' already exists")]
+    SectionAlreadyExists(String),
+    #[error("Key '{section}/{key}' already exists")]
+    KeyAlreadyExists { section: String, key: String },
+    #[error("Invalid key path '{0}', expected Section/Key")]
+    InvalidKeyPath(String),
+    #[error("Invalid value '{0}', expected TYPE=VALUE")]
+    InvalidAssignment(String),
+    #[error("Invalid {attribute} value '{value}'")]
+    InvalidAttributeValue { attribute: String, value: String },
+    #[error("Expected {key} to be {expected}, found {found}")]
+    UnexpectedValue { key: String, expected: String, found: String },
+    #[error("Cannot increment {key} ({value}) by {by}")]
+    InvalidIncrement { key: String, value: String, by: String },
+    #[error("The Signature tag's start tag cannot be rewritten in place, it does not read back as it was found")]
+    SignatureNotRewritable(Option<Span>),
+    #[error("Bank has {} Signature tags in its Bank tag, expected one", .0.len())]
+    MultipleSignatures(Vec<Option<Span>>),
+    #[error("{} unparsable value(s) in bank:{}", .0.len(), .0.iter().map(|v| format!("\n  {}", v)).collect::<String>())]
+    InvalidValues(Vec<InvalidValue>),
+}
+
+/// A value whose text does not parse as its declared type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidValue {
+    pub section: String,
+    pub key: String,
+    pub tag_name: String,
+    pub attribute: String,
+    pub value: String,
+    pub span: Option<Span>,
+}
+
+impl BankParserError {
+    /// The error as source-located diagnostics, one per problem found.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            BankParserError::SectionTagMissingName(span)
+            | BankParserError::KeyTagMissingName(span)
+            | BankParserError::SignatureNotRewritable(span) => {
+                vec![Diagnostic::error(self.to_string(), *span)]
+            }
+            BankParserError::MultipleSignatures(spans) => spans
+                .iter()
+                .map(|span| Diagnostic::error(self.to_string(), *span))
+                .collect(),
+            BankParserError::InvalidValues(invalid_values) => invalid_values
+                .iter()
+                .map(|invalid| Diagnostic::error(invalid.to_string(), invalid.span))
+                .collect(),
+            _ => vec![Diagnostic::error(self.to_string(), None)],
+        }
+    }
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}: <{} {}=\"{}\"> is not a valid {}",
+            self.section, self.key, self.tag_name, self.attribute, self.value, self.attribute
+        )
+    }
+}
+
+/// How values that do not parse as their declared type are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Reject the bank, reporting every unparsable value.
+    #[default]
+    Strict,
+    /// Read unparsable values as the type's default (`0`, `false`) and log a warning,
+    /// for salvaging damaged banks.
+    Lenient,
 }
 
 
 #[derive(Debug, Clone)]
 pub struct BankParser {
-    pub bank_path: BankPath,
+    pub context: SigningContext,
+    pub mode: ParseMode,
+    /// File the bank was read from, `None` for banks parsed from memory.
+    pub source_path: Option<PathBuf>,
+    /// `bank_file::content_hash` of the file at `source_path` as it was read. Writes
+    /// refuse to replace the file once it no longer has this hash; `None` to write
+    /// regardless, e.g. to a new file.
+    pub source_hash: Option<String>,
+    /// How the file is backed up before `save` or `replace_signature` overwrite it.
+    pub backup: BackupPolicy,
+    /// The complete document, in file order, used to write the bank back out.
+    pub document: BankDocument,
     pub sections: Vec<Section>,
+    /// Non-fatal problems found while reading the sections.
+    pub diagnostics: Vec<Diagnostic>,
     pub current_signature: Option<String>,
     pub signature: String,
 }
 impl BankParser {
     /// Parses the bank file, calculates the signature, and returns a BankParser instance.
-    pub fn new(args: &Args) -> AppResult<Self> {
-        let bank_path = BankPath::new(args)?;
-        let file_content = fs::read_to_string(&bank_path.full_path)?;
-
-        let reader = std::io::Cursor::new(file_content);
-        let parser = EventReader::new(reader);
-
-        let mut sections: Vec<Section> = Vec::new();
-        let mut current_signature: Option<String> = None;
-        let mut current_section: Option<Section> = None;
-        let mut current_key: Option<Key> = None;
-
-        for event in parser {
-            match event? {
-                XmlEvent::StartElement {
-                    name, attributes, ..
-                } => {
-                    let tag_name = name.local_name.as_str();
-                    match tag_name {
-                        "Section" => {
-                            if current_section.is_some() {
-                                log::error!("Section already opened");
-                            }
-                            current_section = Some(Section {
-                                name:  attributes
-                                    .iter()
-                                    .find(|attr| attr.name.local_name == "name")
-                                    .map(|attr| attr.value.clone())
-                                    .ok_or(BankParserError::SectionTagMissingName)?,
-                                keys: Vec::new(),
-                            });
-                        }
-                        "Key" => {
-                            if current_key.is_some(){
-                                log::error!("Key already opened");
-                            }
-                            if current_section.is_some() {
-                                current_key = Some(Key {
-                                    name:  attributes
-                                        .iter()
-                                        .find(|attr| attr.name.local_name == "name")
-                                        .map(|attr| attr.value.clone())
-                                        .ok_or(BankParserError::KeyTagMissingName)?,
-                                    values: Vec::new(),
-                                });
-                            } else {
-                                // Log potentially invalid structure
-                                log::warn!("Found Key tag outside of a Section context.");
-                            }
-                        }
-                        "Signature" => {
-                            current_signature = attributes
-                                .iter()
-                                .find(|attr| attr.name.local_name == "value")
-                                .map(|attr| attr.value.clone());
-                        }
-                        _ => {
-                            if let Some(key) = current_key.as_mut() {
-                                key.values.push(ValueElement {
-                                    tag_name: tag_name.to_string(),
-                                    attributes: attributes
-                                        .iter()
-                                        .map(|attr| Attribute::from_xml_attribute(&attr.name.local_name, &attr.value))
-                                        .collect(),
-                                });
-                            }
-                        }
-                    }
-                }
-                XmlEvent::EndElement { name } => {
-                    match name.local_name.as_str() {
-                        "Key" => {
-                            if let Some(mut key) = current_key.take() {
-                                // Sort ValueElements within the key *before* adding to section
-                                key.values.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
-                                if !key.values.is_empty() {
-                                    if let Some(section) = current_section.as_mut() {
-                                        section.keys.push(key);
-                                    } else {
-                                        log::error!("Finished Key processing but no active Section!");
-                                    }
-                                }
-                            }
-                        }
-                        "Section" => {
-                            if let Some(mut section) = current_section.take() {
-                                // Sort Keys within the section *before* adding to global list
-                                section.keys.sort_by(|a, b| a.name.cmp(&b.name));
-                                if !section.keys.is_empty() {
-                                    sections.push(section);
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
+    pub fn new(args: &BankArgs) -> AppResult<Self> {
+        Self::from_bank_path(&BankPath::new(args)?, args.parse.mode())
+    }
+
+    /// Parses the bank file at an already resolved `BankPath`.
+    pub fn from_bank_path(bank_path: &BankPath, mode: ParseMode) -> AppResult<Self> {
+        Self::from_path_with_mode(&bank_path.full_path, bank_path.context(), mode)
+    }
+
+    /// Parses the bank file at `path`, signing it with an explicit context instead of
+    /// deriving one from the directory structure.
+    pub fn from_path_with_context(path: impl AsRef<Path>, context: SigningContext) -> AppResult<Self> {
+        Self::from_path_with_mode(path, context, ParseMode::Strict)
+    }
+
+    pub fn from_path_with_mode(path: impl AsRef<Path>, context: SigningContext, mode: ParseMode) -> AppResult<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(AppError::FileNotFound(path.to_path_buf()));
         }
+        let content = fs::read(path)?;
+        let mut bank_data = Self::from_reader_with_mode(content.as_slice(), context, mode)?;
+        bank_data.source_path = Some(path.to_path_buf());
+        bank_data.source_hash = Some(content_hash(&content));
+        Ok(bank_data)
+    }
 
-        sections.sort_by(|a, b| a.name.cmp(&b.name));
+    /// Parses bank XML held in memory.
+    pub fn from_str(content: &str, context: SigningContext) -> AppResult<Self> {
+        Self::from_reader(content.as_bytes(), context)
+    }
+
+    /// Parses bank XML from any reader, e.g. an uploaded request body.
+    pub fn from_reader<R: Read>(reader: R, context: SigningContext) -> AppResult<Self> {
+        Self::from_reader_with_mode(reader, context, ParseMode::Strict)
+    }
+
+    pub fn from_reader_with_mode<R: Read>(mut reader: R, context: SigningContext, mode: ParseMode) -> AppResult<Self> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+
+        let mut parser = EventReader::new_with_config(
+            source.as_bytes(),
+            ParserConfig::new().ignore_comments(false),
+        );
+        let mut document = DocumentBuilder::new(&source);
+
+        loop {
+            let event = parser.next()?;
+            document.push(&event, parser.position());
+            if let XmlEvent::EndDocument = event {
+                break;
+            }
+        }
 
         let mut bank_data = BankParser {
-            bank_path,
-            sections,
-            current_signature,
+            context,
+            mode,
+            source_path: None,
+            source_hash: None,
+            backup: BackupPolicy::default(),
+            document: document.finish()?,
+            sections: Vec::new(),
+            diagnostics: Vec::new(),
+            current_signature: None,
             signature: String::new(),
         };
 
-        bank_data.compute_signature();
+        bank_data.refresh()?;
 
         Ok(bank_data)
     }
 
+    /// Builds a new, signed bank holding `sections`.
+    ///
+    /// The document is written out and read back, so names that cannot appear in a
+    /// bank are rejected just like they would be in a file.
+    pub fn from_sections(sections: &[Section], context: SigningContext) -> AppResult<Self> {
+        let document = BankDocument::from_sections(sections);
+        let mut bank = Self::from_str(&document.to_xml(), context)?;
+        bank.apply_signature()?;
+        Ok(bank)
+    }
+
+    /// Re-reads the sections and the stored signature from the document and
+    /// recomputes the signature. Called after every change to the document.
+    pub(crate) fn refresh(&mut self) -> Result<(), BankParserError> {
+        let collected = SectionCollector::collect(&self.document.root, self.mode)?;
+        self.sections = collected.sections;
+        self.diagnostics = collected.diagnostics;
+        self.current_signature = collected.signature;
+        self.compute_signature();
+        Ok(())
+    }
+
+    /// Serializes the bank document back to XML, as it currently stands.
+    pub fn to_xml(&self) -> String {
+        self.document.to_xml()
+    }
+
+    /// Stores the computed signature in the document's `<Signature>` element. A bank
+    /// without one gets it added as the last child of `<Bank>`, where the game writes it.
+    pub fn apply_signature(&mut self) -> AppResult<()> {
+        let formatting = self.document.formatting();
+        let signature_index = self.signature_index()?;
+        let root = &mut self.document.root;
+        let index = match signature_index {
+            Some(index) => index,
+            None => root.insert_element(None, Element::new(SIGNATURE_TAG), &formatting, 0),
+        };
+        if let Some(signature_element) = root.child_element_mut(index) {
+            signature_element.set_attribute("value", self.signature.clone());
+        }
+        self.current_signature = Some(self.signature.clone());
+        Ok(())
+    }
+
+    /// Node index of the bank's `<Signature>` element among the children of the root,
+    /// `None` if there is none. More than one is an error, as it is unclear which holds
+    /// the signature.
+    pub fn signature_index(&self) -> Result<Option<usize>, BankParserError> {
+        let indices: Vec<usize> = self
+            .document
+            .root
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, Node::Element(element) if element.name == SIGNATURE_TAG))
+            .map(|(index, _)| index)
+            .collect();
+        match indices.as_slice() {
+            [] => Ok(None),
+            [index] => Ok(Some(*index)),
+            _ => Err(BankParserError::MultipleSignatures(
+                indices
+                    .iter()
+                    .map(|index| match &self.document.root.children[*index] {
+                        Node::Element(element) => element.span,
+                        _ => None,
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Writes the document back to the file it was read from, atomically and only if
+    /// the file did not change since; see `bank_file::write`.
+    pub fn save(&self) -> AppResult<()> {
+        let file_path = self.source_path.as_ref().ok_or(AppError::NoSourcePath)?;
+        bank_file::write(file_path, self.to_xml().as_bytes(), self.source_hash.as_deref(), &self.backup)
+    }
+
+    /// Writes several banks back to the files they were read from, like `save`, all of
+    /// them or none; see `bank_file::write_all`. Each bank is backed up according to its
+    /// own `backup` policy.
+    pub fn save_all(banks: &[&BankParser]) -> AppResult<()> {
+        let contents: Vec<String> = banks.iter().map(|bank| bank.to_xml()).collect();
+        let mut files = Vec::with_capacity(banks.len());
+        for (bank, content) in banks.iter().zip(&contents) {
+            files.push(bank_file::FileWrite {
+                path: bank.source_path.as_deref().ok_or(AppError::NoSourcePath)?,
+                content: content.as_bytes(),
+                expected_hash: bank.source_hash.as_deref(),
+                backup: &bank.backup,
+            });
+        }
+        bank_file::write_all(&files)
+    }
+
     /// Replaces the signature value in the original bank file content.
     /// Assumes the caller has already verified that replacement is desired.
+    ///
+    /// Only the start tag of the `<Signature>` element under `<Bank>` is rewritten, at
+    /// the position the XML reader found it, keeping its quoting and other attributes;
+    /// the rest of the file is left byte for byte. A bank without the element gets one.
+    /// If that start tag cannot be rebuilt from what was read, nothing is written and
+    /// `BankParserError::SignatureNotRewritable` is returned.
     pub fn replace_signature(&self) -> AppResult<()> {
-        if self.current_signature.is_none() {
-            log::warn!("Attempted to replace signature, but no <Signature> tag was found during initial parsing.");
-            return Err(AppError::SignatureNotFound);
-        }
+        let file_path = self.source_path.as_ref().ok_or(AppError::NoSourcePath)?;
+        let element = match self.signature_index()?.map(|index| &self.document.root.children[index]) {
+            Some(Node::Element(element)) => Some(element),
+            _ => None,
+        };
+        // The Signature element where the reader found it.
+        let found = element.and_then(|element| element.span.map(|span| (element, span)));
+        let Some((signature_element, span)) = found else {
+            // Not in the file: write the otherwise unchanged document with it.
+            if element.is_some() {
+                log::info!("Signature tag of {} added after it was read, writing the document", file_path.display());
+            } else {
+                log::info!("No signature in {}, adding one", file_path.display());
+            }
+            let mut signed = self.clone();
+            signed.apply_signature()?;
+            return signed.save();
+        };
+        let new_tag = signature_element
+            .raw_start_tag_with("value", &self.signature)
+            .ok_or(BankParserError::SignatureNotRewritable(signature_element.span))?;
 
         log::info!(
             "Attempting to replace signature in file: {}",
-            self.bank_path.full_path.display() // Use display()
+            file_path.display() // Use display()
         );
-        let file_path = &self.bank_path.full_path;
 
         let content = fs::read_to_string(file_path)?;
+        if let Some(source_hash) = &self.source_hash
+            && content_hash(content.as_bytes()) != *source_hash
+        {
+            return Err(AppError::ChangedOnDisk(file_path.clone()));
+        }
 
-        // Use a static regex or lazy_static for slight performance gain if called often
-        // static SIG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(<Signature\s+value=")([^"]*)("\s*/>)"#).unwrap());
-        // For now, compile it each time is fine.
-        let re = Regex::new(r#"(<Signature\s+value=")([^"]*)("\s*/>)"#)?;
-
-        // Construct the replacement text using the *newly computed* signature
-        let replacement_string = format!("${{1}}{}${{3}}", self.signature);
-
-        let new_content = re.replacen(&content, 1, replacement_string); // Returns String
+        if content.get(span.start..span.end) != signature_element.raw_start_tag.as_deref() {
+            return Err(AppError::ChangedOnDisk(file_path.clone()));
+        }
+        let new_content = format!("{}{}{}", &content[..span.start], new_tag, &content[span.end..]);
 
         if new_content == content {
             log::warn!("Signature replacement resulted in no changes. File not overwritten.");
             return Ok(());
         }
 
-        fs::write(file_path, new_content.as_bytes())?;
+        bank_file::write(file_path, new_content.as_bytes(), self.source_hash.as_deref(), &self.backup)?;
 
         log::info!("Successfully replaced signature in {}", file_path.display());
         Ok(())
     }
 
     /// Compares the signature found in the file (if any) with the newly computed one.
-    pub fn compare_signature(&self) -> bool {
-        match &self.current_signature {
-            Some(file_sig) => {
-                if file_sig == &self.signature {
-                    log::info!("Signature MATCHES: {}", self.signature);
-                    true
-                } else {
-                    log::warn!("Signature MISMATCH:");
-                    log::warn!("  File:     {}", file_sig);
-                    log::warn!("  Computed: {}", self.signature);
-                    false
-                }
-            }
-            None => {
-                log::info!("No existing signature found in the XML file.");
-                log::info!("Computed signature: {}", self.signature);
-                false
-            }
-        }
+    pub fn verify(&self) -> VerificationReport {
+        let signature = BankSignature {
+            computed: self.signature.clone(),
+            stored: self.current_signature.clone(),
+            diagnostics: self.diagnostics.clone(),
+        };
+        VerificationReport::new(self.source_path.clone(), self.context.clone(), signature)
+    }
+
+    /// The signing payload item by item, for finding out why a signature differs.
+    pub fn payload(&self) -> Payload {
+        let mut hasher = PayloadHasher::recording(&self.context);
+        hasher.sections(&self.sections);
+        hasher.finish_payload()
     }
 
     /// Computes the signature string based on the parsed bank data.
     fn compute_signature(&mut self) {
-        let mut pitems: Vec<String> = Vec::new();
-
-        pitems.push(self.bank_path.author_handle.clone());
-        pitems.push(self.bank_path.player_handle.clone());
-        pitems.push(self.bank_path.bank_name.clone());
-
-        for section in &self.sections {
-            pitems.push(section.name.clone());
-            for key in &section.keys {
-                pitems.push(key.name.clone());
-                for value_element in &key.values {
-                    pitems.push(value_element.tag_name.clone());
-
-                    let mut attrs_to_sort: Vec<(String, &Attribute)> = value_element
-                        .attributes
-                        .iter()
-                        .map(|attr| (attr.name(), attr))
-                        .collect();
-
-                    attrs_to_sort.sort_unstable_by(|(name_a, _), (name_b, _)| name_a.cmp(name_b));
-                    for (attr_name, attr) in attrs_to_sort {
-                        pitems.push(attr_name);
-                        if !attr.is_text() {
-                            pitems.push(attr.value());
-                        }
-                    }
-                }
-            }
+        let mut hasher = PayloadHasher::new(&self.context);
+        hasher.sections(&self.sections);
+        self.signature = hasher.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = concat!(
+        "<Bank version='1'>\n",
+        "  <Section name=\"A\"><Key name=\"K\"><Value int=\"1\"/></Key></Section>\n",
+    );
+
+    /// A bank file in a scratch directory, removed when dropped.
+    struct BankFile {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl BankFile {
+        fn new(test: &str, content: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("sc2_bank_signer-bank_parser-{}-{}", std::process::id(), test));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("Bank.SC2Bank");
+            fs::write(&path, content).unwrap();
+            BankFile { dir, path }
+        }
+
+        fn read(&self) -> BankParser {
+            let author = "1-S2-1-1234567".parse().unwrap();
+            let context = SigningContext::new("Bank", author, "2-S2-1-7654321".parse().unwrap());
+            let mut bank = BankParser::from_path_with_context(&self.path, context).unwrap();
+            bank.backup = BackupPolicy::disabled();
+            bank
+        }
+
+        fn content(&self) -> String {
+            fs::read_to_string(&self.path).unwrap()
         }
+    }
+
+    impl Drop for BankFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
 
-        let payload = pitems.join("");
-        let mut hasher = Sha1::new();
-        hasher.update(payload.as_bytes());
-        let hash_result = hasher.finalize();
+    #[test]
+    fn replaces_only_the_signature_value() {
+        let file = BankFile::new("replace", &format!("{}  <Signature  value='OLD' extra=\"x\"/>\n</Bank>", CONTENT));
+        let bank = file.read();
+        bank.replace_signature().unwrap();
+        let expected = format!("{}  <Signature  value='{}' extra=\"x\"/>\n</Bank>", CONTENT, bank.signature);
+        assert_eq!(file.content(), expected);
+    }
 
-        self.signature = hex::encode_upper(hash_result);
+    #[test]
+    fn adds_a_missing_signature() {
+        let file = BankFile::new("add", &format!("{}</Bank>", CONTENT));
+        let bank = file.read();
+        bank.replace_signature().unwrap();
+        assert_eq!(file.read().current_signature.as_deref(), Some(bank.signature.as_str()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn writes_a_signature_added_after_reading() {
+        let file = BankFile::new("added", &format!("{}</Bank>", CONTENT));
+        let mut bank = file.read();
+        bank.apply_signature().unwrap();
+        bank.replace_signature().unwrap();
+        assert_eq!(file.read().current_signature.as_deref(), Some(bank.signature.as_str()));
+    }
+
+    #[test]
+    fn refuses_a_signature_tag_it_cannot_rebuild() {
+        let content = format!("{}  <Signature value=\"OLD\"/>\n</Bank>", CONTENT);
+        let file = BankFile::new("rebuild", &content);
+        let mut bank = file.read();
+        let index = bank.signature_index().unwrap().unwrap();
+        if let Node::Element(element) = &mut bank.document.root.children[index] {
+            element.raw_start_tag = None;
+        }
+        let err = bank.replace_signature().unwrap_err();
+        assert!(matches!(err, AppError::BankParseError(BankParserError::SignatureNotRewritable(Some(_)))));
+        assert_eq!(file.content(), content);
+    }
+}
@@ -1,6 +1,9 @@
 
 use crate::bank_parser::fixed::Fixed;
 use crate::bank_parser::flag::Flag;
+use crate::bank_parser::BankParserError;
+use std::fmt;
+use std::str::FromStr;
 
 
 const TEXT_ATTRIBUTE: &str = "text";
@@ -9,11 +12,14 @@ const INTEGER_ATTRIBUTE: &str = "int";
 const FIXED_ATTRIBUTE: &str = "fixed";
 const FLAG_ATTRIBUTE: &str = "flag";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Attribute {
     String(String),
     Int(i32),
-    Fixed(Fixed),
+    /// A `fixed` value, with the text it was read from if formatting the value does
+    /// not give that text back, e.g. `0.2999` or `1.50`. The text is what is written
+    /// and signed; the value is for typed access.
+    Fixed(Fixed, Option<String>),
     Flag(Flag),
     Text(String),
     Custom(String, String),
@@ -24,10 +30,64 @@ impl Attribute {
         matches!(self, Attribute::Text(_))
     }
 
+    /// Whether `other` holds the same typed value. Unlike `==`, a `fixed` value is the
+    /// same whatever text it was read from, e.g. `1.50` and `1.5`.
+    pub fn same_value(&self, other: &Attribute) -> bool {
+        match (self, other) {
+            (Attribute::Fixed(a, _), Attribute::Fixed(b, _)) => a == b,
+            _ => self == other,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            Attribute::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// A `fixed` value read from `text`, keeping the text unless it is how the value
+    /// formats anyway.
+    pub fn fixed_from_text(value: Fixed, text: &str) -> Self {
+        let text = (value.to_string() != text).then(|| text.to_string());
+        Attribute::Fixed(value, text)
+    }
+
+    pub fn as_fixed(&self) -> Option<&Fixed> {
+        match self {
+            Attribute::Fixed(v, _) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_flag(&self) -> Option<bool> {
+        match self {
+            Attribute::Flag(v) => Some(v.0),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Attribute::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Attribute::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+
     pub fn from_xml_attribute(name: &str, value: &str) -> Self {
         match name {
             INTEGER_ATTRIBUTE => Attribute::Int(value.parse().unwrap_or(0)),
-            FIXED_ATTRIBUTE => Attribute::Fixed(Fixed::from(value)),
+            FIXED_ATTRIBUTE => match value.parse() {
+                Ok(fixed) => Attribute::fixed_from_text(fixed, value),
+                Err(_) => Attribute::Fixed(Fixed::ZERO, None),
+            },
             FLAG_ATTRIBUTE => Attribute::Flag(Flag::from(value)),
             TEXT_ATTRIBUTE => Attribute::Text(value.to_string()),
             STRING_ATTRIBUTE => Attribute::String(value.to_string()),
@@ -35,25 +95,133 @@ impl Attribute {
         }
     }
 
+    /// Parses a value of attribute type `name`, rejecting values that do not parse
+    /// instead of falling back to a default.
+    pub fn parse(name: &str, value: &str) -> Result<Self, BankParserError> {
+        let invalid = || BankParserError::InvalidAttributeValue {
+            attribute: name.to_string(),
+            value: value.to_string(),
+        };
+        Ok(match name {
+            INTEGER_ATTRIBUTE => Attribute::Int(value.parse().map_err(|_| invalid())?),
+            FIXED_ATTRIBUTE => Attribute::fixed_from_text(value.parse().map_err(|_| invalid())?, value),
+            FLAG_ATTRIBUTE => Attribute::Flag(value.parse().map_err(|_| invalid())?),
+            _ => Self::from_xml_attribute(name, value),
+        })
+    }
+
     pub fn value(&self) -> String {
+        let mut value = String::new();
+        let _ = self.write_value(&mut value);
+        value
+    }
+
+    /// Writes the value as it appears in the bank, without allocating.
+    pub fn write_value<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
         match self {
-            Attribute::Int(v) => v.to_string(),
-            Attribute::Fixed(v) => v.to_string(),
-            Attribute::Flag(v) => v.to_string(),
-            Attribute::Text(v) => v.clone(),
-            Attribute::String(v) => v.clone(),
-            Attribute::Custom(_, value) => value.clone(),
+            Attribute::Int(v) => write!(out, "{}", v),
+            Attribute::Fixed(_, Some(text)) => out.write_str(text),
+            Attribute::Fixed(v, None) => write!(out, "{}", v),
+            Attribute::Flag(v) => write!(out, "{}", v),
+            Attribute::Text(v) => out.write_str(v),
+            Attribute::String(v) => out.write_str(v),
+            Attribute::Custom(_, value) => out.write_str(value),
         }
     }
 
-    pub fn name(&self) -> String {
+    pub fn name(&self) -> &str {
         match self {
-            Attribute::Int(_) => INTEGER_ATTRIBUTE.to_string(),
-            Attribute::Fixed(_) => FIXED_ATTRIBUTE.to_string(),
-            Attribute::Flag(_) => FLAG_ATTRIBUTE.to_string(),
-            Attribute::Text(_) => TEXT_ATTRIBUTE.to_string(),
-            Attribute::String(_) => STRING_ATTRIBUTE.to_string(),
-            Attribute::Custom(name, _) => name.clone(), // Borrow name here
+            Attribute::Int(_) => INTEGER_ATTRIBUTE,
+            Attribute::Fixed(..) => FIXED_ATTRIBUTE,
+            Attribute::Flag(_) => FLAG_ATTRIBUTE,
+            Attribute::Text(_) => TEXT_ATTRIBUTE,
+            Attribute::String(_) => STRING_ATTRIBUTE,
+            Attribute::Custom(name, _) => name,
         }
     }
 }
+
+impl fmt::Display for Attribute {
+    /// Writes the `type=value` assignment `from_str` reads.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=", self.name())?;
+        self.write_value(f)
+    }
+}
+
+impl FromStr for Attribute {
+    type Err = BankParserError;
+
+    /// Parses a `type=value` assignment such as `int=42`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| BankParserError::InvalidAssignment(s.to_string()))?;
+        Attribute::parse(name, value)
+    }
+}
+
+/// Attributes serialize as `{"name": "int", "value": 42}`: `int` values as numbers,
+/// `flag` values as booleans, `fixed` values as their exact decimal text and all
+/// other values as strings.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Attribute {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Attribute", 2)?;
+        state.serialize_field("name", self.name())?;
+        match self {
+            Attribute::Int(v) => state.serialize_field("value", v)?,
+            Attribute::Fixed(..) => state.serialize_field("value", &self.value())?,
+            Attribute::Flag(v) => state.serialize_field("value", &v.0)?,
+            Attribute::Text(v) | Attribute::String(v) | Attribute::Custom(_, v) => {
+                state.serialize_field("value", v)?
+            }
+        }
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerializedAttribute {
+    name: String,
+    value: SerializedValue,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum SerializedValue {
+    Bool(bool),
+    Int(i64),
+    Number(f64),
+    String(String),
+}
+
+/// Accepts what `Serialize` writes. `fixed` values may also be given as numbers,
+/// which are rounded to the nearest 1/4096.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Attribute {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let SerializedAttribute { name, value } = SerializedAttribute::deserialize(deserializer)?;
+        let attribute = match (name.as_str(), &value) {
+            (INTEGER_ATTRIBUTE, SerializedValue::Int(v)) => i32::try_from(*v).ok().map(Attribute::Int),
+            (FIXED_ATTRIBUTE, SerializedValue::String(v)) => {
+                v.parse().ok().map(|fixed| Attribute::fixed_from_text(fixed, v))
+            }
+            (FIXED_ATTRIBUTE, SerializedValue::Int(v)) => {
+                i32::try_from(*v).ok().and_then(Fixed::from_int).map(|fixed| Attribute::Fixed(fixed, None))
+            }
+            (FIXED_ATTRIBUTE, SerializedValue::Number(v)) => Some(Attribute::Fixed(Fixed::new(*v), None)),
+            (FLAG_ATTRIBUTE, SerializedValue::Bool(v)) => Some(Attribute::Flag(Flag(*v))),
+            (INTEGER_ATTRIBUTE | FIXED_ATTRIBUTE | FLAG_ATTRIBUTE, _) => None,
+            (_, SerializedValue::String(v)) => Some(Attribute::from_xml_attribute(&name, v)),
+            _ => None,
+        };
+        attribute.ok_or_else(|| D::Error::custom(format!("invalid value {:?} for attribute '{}'", value, name)))
+    }
+}
@@ -0,0 +1,199 @@
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::diagnostic::Diagnostic;
+use crate::bank_parser::document::Element;
+use crate::bank_parser::key::Key;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::{
+    BankParserError, InvalidValue, ParseMode, KEY_TAG, NAME_ATTRIBUTE, SECTION_TAG, SIGNATURE_TAG,
+};
+
+/// What a walk over the document found.
+#[derive(Debug)]
+pub(crate) struct Collected {
+    pub(crate) sections: Vec<Section>,
+    pub(crate) signature: Option<String>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+/// Extracts the sorted sections that go into the signature, and the signature stored
+/// in the file, by walking a bank document in file order.
+#[derive(Debug, Default)]
+pub(crate) struct SectionCollector {
+    mode: ParseMode,
+    invalid_values: Vec<InvalidValue>,
+    diagnostics: Vec<Diagnostic>,
+    sections: Vec<Section>,
+    signature: Option<String>,
+    /// Whether the bank's `<Signature>` element, a child of `<Bank>`, was seen.
+    signature_found: bool,
+    /// Nesting depth of the element being visited, the root being 0.
+    depth: usize,
+    /// Whether to keep sections and keys without values, which are not signed.
+    keep_empty: bool,
+    current_section: Option<Section>,
+    current_key: Option<Key>,
+}
+
+impl SectionCollector {
+    /// In strict mode, fails with every value that does not parse as its declared type.
+    /// In lenient mode such values are logged and read with a default instead.
+    pub(crate) fn collect(
+        root: &Element,
+        mode: ParseMode,
+    ) -> Result<Collected, BankParserError> {
+        SectionCollector {
+            mode,
+            ..SectionCollector::default()
+        }
+        .run(root)
+    }
+
+    /// Like `collect`, but keeps the sections and keys that have no values.
+    #[cfg(feature = "serde")]
+    pub(crate) fn collect_all(
+        root: &Element,
+        mode: ParseMode,
+    ) -> Result<Collected, BankParserError> {
+        SectionCollector {
+            mode,
+            keep_empty: true,
+            ..SectionCollector::default()
+        }
+        .run(root)
+    }
+
+    fn run(mut self, root: &Element) -> Result<Collected, BankParserError> {
+        self.visit(root)?;
+        if !self.invalid_values.is_empty() {
+            return Err(BankParserError::InvalidValues(self.invalid_values));
+        }
+        self.sections.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Collected {
+            sections: self.sections,
+            signature: self.signature,
+            diagnostics: self.diagnostics,
+        })
+    }
+
+    fn visit(&mut self, element: &Element) -> Result<(), BankParserError> {
+        self.start_element(element)?;
+        self.depth += 1;
+        for child in element.elements() {
+            self.visit(child)?;
+        }
+        self.depth -= 1;
+        self.end_element(element);
+        Ok(())
+    }
+
+    fn start_element(&mut self, element: &Element) -> Result<(), BankParserError> {
+        let tag_name = element.name.as_str();
+        match tag_name {
+            SECTION_TAG => {
+                if self.current_section.is_some() {
+                    self.warn("Section already opened", element);
+                }
+                self.current_section = Some(Section {
+                    name: element
+                        .attribute(NAME_ATTRIBUTE)
+                        .map(|name| name.to_string())
+                        .ok_or(BankParserError::SectionTagMissingName(element.span))?,
+                    keys: Vec::new(),
+                });
+            }
+            KEY_TAG => {
+                if self.current_key.is_some() {
+                    self.warn("Key already opened", element);
+                }
+                if self.current_section.is_some() {
+                    self.current_key = Some(Key {
+                        name: element
+                            .attribute(NAME_ATTRIBUTE)
+                            .map(|name| name.to_string())
+                            .ok_or(BankParserError::KeyTagMissingName(element.span))?,
+                        values: Vec::new(),
+                    });
+                } else {
+                    // Report potentially invalid structure
+                    self.warn("Found Key tag outside of a Section context", element);
+                }
+            }
+            SIGNATURE_TAG => {
+                if self.depth != 1 {
+                    self.warn("Misplaced Signature tag: only a direct child of the Bank tag holds the signature, this one is ignored", element);
+                } else if self.signature_found {
+                    self.diagnostics.push(Diagnostic::error(
+                        "Another Signature tag in the Bank tag: the bank's signature is ambiguous",
+                        element.span,
+                    ));
+                } else {
+                    self.signature_found = true;
+                    self.signature = element.attribute("value").map(|value| value.to_string());
+                }
+            }
+            _ => {
+                if let Some(key) = self.current_key.as_mut() {
+                    let mut attributes = Vec::with_capacity(element.attributes.len());
+                    for attr in &element.attributes {
+                        if Attribute::parse(&attr.name, &attr.value).is_err() {
+                            let invalid = InvalidValue {
+                                section: self.current_section.as_ref().map(|s| s.name.clone()).unwrap_or_default(),
+                                key: key.name.clone(),
+                                tag_name: tag_name.to_string(),
+                                attribute: attr.name.clone(),
+                                value: attr.value.clone(),
+                                span: element.attribute_span(&attr.name).or(element.span),
+                            };
+                            match self.mode {
+                                ParseMode::Strict => self.invalid_values.push(invalid),
+                                ParseMode::Lenient => self.diagnostics.push(Diagnostic::warning(
+                                    format!("{}, read as default", invalid),
+                                    invalid.span,
+                                )),
+                            }
+                        }
+                        attributes.push(Attribute::from_xml_attribute(&attr.name, &attr.value));
+                    }
+                    key.values.push(ValueElement {
+                        tag_name: tag_name.to_string(),
+                        attributes,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_element(&mut self, element: &Element) {
+        match element.name.as_str() {
+            KEY_TAG => {
+                if let Some(mut key) = self.current_key.take() {
+                    // Sort ValueElements within the key *before* adding to section
+                    key.values.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+                    if self.keep_empty || !key.values.is_empty() {
+                        if let Some(section) = self.current_section.as_mut() {
+                            section.keys.push(key);
+                        } else {
+                            self.warn("Finished Key processing but no active Section", element);
+                        }
+                    }
+                }
+            }
+            SECTION_TAG => {
+                if let Some(mut section) = self.current_section.take() {
+                    // Sort Keys within the section *before* adding to global list
+                    section.keys.sort_by(|a, b| a.name.cmp(&b.name));
+                    if self.keep_empty || !section.keys.is_empty() {
+                        self.sections.push(section);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn warn(&mut self, message: &str, element: &Element) {
+        self.diagnostics.push(Diagnostic::warning(message, element.span));
+    }
+}
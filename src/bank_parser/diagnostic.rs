@@ -0,0 +1,128 @@
+use crate::bank_parser::source_map::UTF8_BOM;
+use std::fmt;
+use std::fmt::Write;
+
+/// Location of a piece of bank source text: a byte range, and the 1-based line and
+/// character column where it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a bank, with the place in the source it was found at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Option<Span>) -> Self {
+        Diagnostic { severity: Severity::Error, message: message.into(), span }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Option<Span>) -> Self {
+        Diagnostic { severity: Severity::Warning, message: message.into(), span }
+    }
+
+    /// Renders the diagnostic compiler-style: the message, the location, and the
+    /// offending source line with the span underlined.
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.message);
+        let Some(span) = self.span else {
+            let _ = writeln!(out, " --> {}", file_name);
+            return out;
+        };
+        let gutter = " ".repeat(span.line.to_string().len());
+        let _ = writeln!(out, "{}--> {}:{}:{}", gutter, file_name, span.line, span.column);
+
+        let Some(line_text) = source.lines().nth(span.line - 1) else {
+            return out;
+        };
+        let line_text = line_text.trim_end_matches('\r');
+        // Columns do not count a leading BOM, see `SourceMap::new`.
+        let line_text = match span.line {
+            1 => line_text.strip_prefix(UTF8_BOM).unwrap_or(line_text),
+            _ => line_text,
+        };
+        let underline_start = span.column - 1;
+        let line_chars = line_text.chars().count();
+        let span_chars = source
+            .get(span.start..span.end)
+            .map(|text| text.chars().count())
+            .unwrap_or(1);
+        let underline_len = span_chars.min(line_chars.saturating_sub(underline_start)).max(1);
+        let indent: String = line_text
+            .chars()
+            .take(underline_start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let _ = writeln!(out, "{} |", gutter);
+        let _ = writeln!(out, "{} | {}", span.line, line_text);
+        let _ = writeln!(out, "{} | {}{}", gutter, indent, "^".repeat(underline_len));
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}:{}: {}: {}", span.line, span.column, self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank_parser::source_map::SourceMap;
+
+    /// The diagnostic for the first `needle` in `source`, rendered.
+    fn render_at(source: &str, needle: &str) -> String {
+        let start = source.find(needle).unwrap();
+        let span = SourceMap::new(source).span(source, start, start + needle.len());
+        Diagnostic::error("bad", Some(span)).render(source, "Bank.SC2Bank")
+    }
+
+    #[test]
+    fn underlines_the_span() {
+        let rendered = render_at("<Bank>\n  <Key name=\"K\"/>\n</Bank>", "<Key");
+        assert_eq!(rendered, "error: bad\n --> Bank.SC2Bank:2:3\n  |\n2 |   <Key name=\"K\"/>\n  |   ^^^^\n");
+    }
+
+    #[test]
+    fn keeps_tabs_in_the_indent() {
+        let rendered = render_at("<Bank>\n\t<Key/>\n</Bank>", "<Key/>");
+        assert!(rendered.ends_with("2 | \t<Key/>\n  | \t^^^^^^\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn leaves_out_the_bom_on_the_first_line() {
+        let rendered = render_at("\u{feff}<Bank><Key/></Bank>", "<Key/>");
+        assert_eq!(rendered, "error: bad\n --> Bank.SC2Bank:1:7\n  |\n1 | <Bank><Key/></Bank>\n  |       ^^^^^^\n");
+    }
+}
@@ -0,0 +1,396 @@
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::key::Key;
+use crate::bank_parser::key_path::KeyPath;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::{BankParser, VALUE_TAG};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// What changed between two versions of a bank.
+///
+/// Banks are compared on the parsed `Section`/`Key`/`ValueElement` model rather than
+/// on their text, so indentation and the order of sections, keys and attributes do not
+/// show up as changes. Changes are listed in section and key order.
+///
+/// Like `BankMerge`, sections sharing a name are compared as one, and a key listed more
+/// than once in a section is compared listing by listing: the first of them in the old
+/// bank with the first in the new one, and so on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BankDiff {
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A section only the new bank has. Each of its keys follows as `KeyAdded`.
+    SectionAdded(String),
+    /// A section only the old bank has. Each of its keys follows as `KeyRemoved`.
+    SectionRemoved(String),
+    KeyAdded { key: KeyPath, values: Vec<ValueElement> },
+    KeyRemoved { key: KeyPath, values: Vec<ValueElement> },
+    /// A value element only the new version of a key has.
+    ElementAdded { key: KeyPath, element: ValueElement },
+    /// A value element only the old version of a key has.
+    ElementRemoved { key: KeyPath, element: ValueElement },
+    /// An attribute of a value element that was added (`old` is `None`), removed (`new`
+    /// is `None`) or changed. A change of type, e.g. from `int` to `fixed`, is a single
+    /// change.
+    ValueChanged {
+        key: KeyPath,
+        tag_name: String,
+        old: Option<Attribute>,
+        new: Option<Attribute>,
+    },
+}
+
+impl BankDiff {
+    pub fn between(old: &BankParser, new: &BankParser) -> Self {
+        Self::new(&old.sections, &new.sections)
+    }
+
+    pub fn new(old: &[Section], new: &[Section]) -> Self {
+        let mut changes = Vec::new();
+        let old_sections = keys_by_section(old);
+        let new_sections = keys_by_section(new);
+
+        for name in union(&old_sections, &new_sections) {
+            match (old_sections.get(name), new_sections.get(name)) {
+                (Some(old_keys), None) => {
+                    changes.push(Change::SectionRemoved(name.to_string()));
+                    for key in old_keys {
+                        changes.push(Change::KeyRemoved {
+                            key: KeyPath::new(name, &key.name),
+                            values: key.values.clone(),
+                        });
+                    }
+                }
+                (None, Some(new_keys)) => {
+                    changes.push(Change::SectionAdded(name.to_string()));
+                    for key in new_keys {
+                        changes.push(Change::KeyAdded {
+                            key: KeyPath::new(name, &key.name),
+                            values: key.values.clone(),
+                        });
+                    }
+                }
+                (Some(old_keys), Some(new_keys)) => diff_keys(name, old_keys, new_keys, &mut changes),
+                (None, None) => {}
+            }
+        }
+        BankDiff { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn diff_keys(section: &str, old: &[&Key], new: &[&Key], changes: &mut Vec<Change>) {
+    let old_keys = by_occurrence(old);
+    let new_keys = by_occurrence(new);
+
+    for slot in union(&old_keys, &new_keys) {
+        let key = KeyPath::new(section, slot.0);
+        match (old_keys.get(&slot), new_keys.get(&slot)) {
+            (Some(old_key), None) => changes.push(Change::KeyRemoved { key, values: old_key.values.clone() }),
+            (None, Some(new_key)) => changes.push(Change::KeyAdded { key, values: new_key.values.clone() }),
+            (Some(old_key), Some(new_key)) => diff_values(&key, &old_key.values, &new_key.values, changes),
+            (None, None) => {}
+        }
+    }
+}
+
+/// Pairs value elements by tag name, and elements sharing a tag name by position.
+fn diff_values(key: &KeyPath, old: &[ValueElement], new: &[ValueElement], changes: &mut Vec<Change>) {
+    let old_tags = by_tag(old);
+    let new_tags = by_tag(new);
+
+    for tag in union(&old_tags, &new_tags) {
+        let old_elements = old_tags.get(tag).map_or(&[][..], Vec::as_slice);
+        let new_elements = new_tags.get(tag).map_or(&[][..], Vec::as_slice);
+        for (old_element, new_element) in old_elements.iter().zip(new_elements) {
+            diff_attributes(key, tag, &old_element.attributes, &new_element.attributes, changes);
+        }
+        for element in old_elements.iter().skip(new_elements.len()) {
+            changes.push(Change::ElementRemoved { key: key.clone(), element: (*element).clone() });
+        }
+        for element in new_elements.iter().skip(old_elements.len()) {
+            changes.push(Change::ElementAdded { key: key.clone(), element: (*element).clone() });
+        }
+    }
+}
+
+fn diff_attributes(key: &KeyPath, tag_name: &str, old: &[Attribute], new: &[Attribute], changes: &mut Vec<Change>) {
+    let mut change = |old: Option<&Attribute>, new: Option<&Attribute>| {
+        changes.push(Change::ValueChanged {
+            key: key.clone(),
+            tag_name: tag_name.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+        })
+    };
+
+    for old_attribute in old {
+        if let Some(new_attribute) = new.iter().find(|a| a.name() == old_attribute.name())
+            && old_attribute != new_attribute
+        {
+            change(Some(old_attribute), Some(new_attribute));
+        }
+    }
+
+    match (old, new) {
+        // The value's type changed.
+        ([old_attribute], [new_attribute]) if old_attribute.name() != new_attribute.name() => {
+            change(Some(old_attribute), Some(new_attribute))
+        }
+        _ => {
+            let removed = old.iter().filter(|a| !new.iter().any(|b| b.name() == a.name()));
+            let added = new.iter().filter(|a| !old.iter().any(|b| b.name() == a.name()));
+            for attribute in removed {
+                change(Some(attribute), None);
+            }
+            for attribute in added {
+                change(None, Some(attribute));
+            }
+        }
+    }
+}
+
+fn by_tag(elements: &[ValueElement]) -> BTreeMap<&str, Vec<&ValueElement>> {
+    let mut tags: BTreeMap<&str, Vec<&ValueElement>> = BTreeMap::new();
+    for element in elements {
+        tags.entry(element.tag_name.as_str()).or_default().push(element);
+    }
+    tags
+}
+
+/// The keys of each section by section name, those of sections sharing a name together.
+fn keys_by_section(sections: &[Section]) -> BTreeMap<&str, Vec<&Key>> {
+    let mut keys: BTreeMap<&str, Vec<&Key>> = BTreeMap::new();
+    for section in sections {
+        keys.entry(section.name.as_str()).or_default().extend(&section.keys);
+    }
+    keys
+}
+
+/// Keys by name and occurrence of the name, from 0.
+fn by_occurrence<'a>(keys: &[&'a Key]) -> BTreeMap<(&'a str, usize), &'a Key> {
+    let mut listed: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut map = BTreeMap::new();
+    for key in keys {
+        let occurrence = listed.entry(key.name.as_str()).or_default();
+        map.insert((key.name.as_str(), *occurrence), *key);
+        *occurrence += 1;
+    }
+    map
+}
+
+fn union<K: Ord + Copy, A, B>(a: &BTreeMap<K, A>, b: &BTreeMap<K, B>) -> BTreeSet<K> {
+    a.keys().chain(b.keys()).copied().collect()
+}
+
+/// The difference `new - old` of two numbers of the same type, signed.
+fn delta(old: &Attribute, new: &Attribute) -> Option<String> {
+    match (old, new) {
+        (Attribute::Int(old), Attribute::Int(new)) => Some(format!("{:+}", i64::from(*new) - i64::from(*old))),
+        (Attribute::Fixed(old, _), Attribute::Fixed(new, _)) => {
+            let delta = new.checked_sub(*old)?;
+            let sign = if delta.raw() > 0 { "+" } else { "" };
+            Some(format!("{}{}", sign, delta))
+        }
+        _ => None,
+    }
+}
+
+fn write_attribute(f: &mut fmt::Formatter<'_>, attribute: &Attribute) -> fmt::Result {
+    write!(f, "{}={}", attribute.name(), attribute.value().escape_debug())
+}
+
+fn write_element(f: &mut fmt::Formatter<'_>, element: &ValueElement) -> fmt::Result {
+    write!(f, "<{}", element.tag_name)?;
+    for attribute in &element.attributes {
+        write!(f, " ")?;
+        write_attribute(f, attribute)?;
+    }
+    write!(f, ">")
+}
+
+/// A key's values: just `type=value` for the usual single `<Value>` element.
+pub(crate) fn write_values(f: &mut fmt::Formatter<'_>, values: &[ValueElement]) -> fmt::Result {
+    if let [element] = values
+        && let (VALUE_TAG, [attribute]) = (element.tag_name.as_str(), element.attributes.as_slice())
+    {
+        return write_attribute(f, attribute);
+    }
+    for (index, element) in values.iter().enumerate() {
+        if index > 0 {
+            write!(f, " ")?;
+        }
+        write_element(f, element)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::SectionAdded(name) => write!(f, "+ [{}]", name),
+            Change::SectionRemoved(name) => write!(f, "- [{}]", name),
+            Change::KeyAdded { key, values } => {
+                write!(f, "+ {}: ", key)?;
+                write_values(f, values)
+            }
+            Change::KeyRemoved { key, values } => {
+                write!(f, "- {}: ", key)?;
+                write_values(f, values)
+            }
+            Change::ElementAdded { key, element } => {
+                write!(f, "+ {}: ", key)?;
+                write_element(f, element)
+            }
+            Change::ElementRemoved { key, element } => {
+                write!(f, "- {}: ", key)?;
+                write_element(f, element)
+            }
+            Change::ValueChanged { key, tag_name, old, new } => {
+                let marker = match (old, new) {
+                    (None, _) => '+',
+                    (_, None) => '-',
+                    _ => '~',
+                };
+                write!(f, "{} {}", marker, key)?;
+                if tag_name != VALUE_TAG {
+                    write!(f, " <{}>", tag_name)?;
+                }
+                write!(f, ": ")?;
+                match (old, new) {
+                    (Some(old), Some(new)) if old.name() == new.name() => {
+                        write!(
+                            f,
+                            "{} {} -> {}",
+                            old.name(),
+                            old.value().escape_debug(),
+                            new.value().escape_debug()
+                        )?;
+                        if let Some(delta) = delta(old, new) {
+                            write!(f, " ({})", delta)?;
+                        }
+                        Ok(())
+                    }
+                    (Some(old), Some(new)) => {
+                        write_attribute(f, old)?;
+                        write!(f, " -> ")?;
+                        write_attribute(f, new)
+                    }
+                    (Some(attribute), None) | (None, Some(attribute)) => write_attribute(f, attribute),
+                    (None, None) => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// A bank as one `Section/Key: values` line per key in signing order, without the
+/// signature: a readable form that diffs well line by line.
+pub struct Listing<'a>(pub &'a [Section]);
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for section in self.0 {
+            for key in &section.keys {
+                write!(f, "{}/{}: ", section.name, key.name)?;
+                write_values(f, &key.values)?;
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BankDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_context::SigningContext;
+
+    /// A `<Section>` holding `keys`, each `(name, attributes of its <Value>)`.
+    fn section(name: &str, keys: &[(&str, &str)]) -> String {
+        let keys: String =
+            keys.iter().map(|(key, value)| format!(r#"<Key name="{}"><Value {}/></Key>"#, key, value)).collect();
+        format!(r#"<Section name="{}">{}</Section>"#, name, keys)
+    }
+
+    fn sections(xml: &str) -> Vec<Section> {
+        BankParser::from_str(&format!(r#"<Bank version="1">{}</Bank>"#, xml), SigningContext::unsigned("Bank"))
+            .unwrap()
+            .sections
+    }
+
+    /// The changes between the banks holding `old` and `new`, as printed.
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        BankDiff::new(&sections(old), &sections(new)).changes.iter().map(Change::to_string).collect()
+    }
+
+    #[test]
+    fn lists_added_removed_and_changed_values() {
+        let old = section("A", &[("K", r#"int="1""#), ("Gone", r#"string="x""#)])
+            + &section("Old", &[("K", r#"flag="1""#)]);
+        let new = section("A", &[("K", r#"string="one""#), ("New", r#"text="y""#)])
+            + &section("B", &[("K", r#"int="2""#)]);
+        assert_eq!(
+            diff(&old, &new),
+            [
+                "- A/Gone: string=x",
+                "~ A/K: int=1 -> string=one",
+                "+ A/New: text=y",
+                "+ [B]",
+                "+ B/K: int=2",
+                "- [Old]",
+                "- Old/K: flag=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn shows_the_difference_of_numbers() {
+        let old = section("S", &[("Gold", r#"int="100""#), ("Rate", r#"fixed="1.5""#)]);
+        let new = section("S", &[("Gold", r#"int="90""#), ("Rate", r#"fixed="1.75""#)]);
+        assert_eq!(diff(&old, &new), ["~ S/Gold: int 100 -> 90 (-10)", "~ S/Rate: fixed 1.5 -> 1.75 (+0.25)"]);
+        let old = section("S", &[("Gold", r#"int="-2147483648""#)]);
+        let new = section("S", &[("Gold", r#"int="2147483647""#)]);
+        assert_eq!(diff(&old, &new), ["~ S/Gold: int -2147483648 -> 2147483647 (+4294967295)"]);
+    }
+
+    #[test]
+    fn reordering_is_not_a_change() {
+        let old = section("A", &[("K", r#"int="1" string="a""#), ("L", r#"int="2""#)])
+            + &section("B", &[("K", r#"flag="0""#)]);
+        let new = "<Section name=\"B\">\n  <Key name=\"K\"><Value flag=\"0\"/></Key>\n</Section>".to_string()
+            + &section("A", &[("L", r#"int="2""#), ("K", r#"string="a" int="1""#)]);
+        assert!(BankDiff::new(&sections(&old), &sections(&new)).is_empty());
+    }
+
+    #[test]
+    fn compares_keys_listed_more_than_once_in_order() {
+        let old = section("A", &[("K", r#"int="1""#), ("K", r#"int="2""#)]);
+        let new = section("A", &[("K", r#"int="1""#), ("K", r#"int="5""#), ("K", r#"int="6""#)]);
+        assert_eq!(diff(&old, &new), ["~ A/K: int 2 -> 5 (+3)", "+ A/K: int=6"]);
+        assert_eq!(diff(&new, &old), ["~ A/K: int 5 -> 2 (-3)", "- A/K: int=6"]);
+    }
+
+    #[test]
+    fn compares_sections_listed_more_than_once_as_one() {
+        let old = section("A", &[("K", r#"int="1""#)]) + &section("A", &[("L", r#"int="2""#)]);
+        let new = section("A", &[("K", r#"int="1""#), ("L", r#"int="3""#)]);
+        assert_eq!(diff(&old, &new), ["~ A/L: int 2 -> 3 (+1)"]);
+    }
+}
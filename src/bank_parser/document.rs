@@ -0,0 +1,654 @@
+use crate::bank_parser::diagnostic::Span;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::source_map::SourceMap;
+use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::{BankParserError, KEY_TAG, NAME_ATTRIBUTE, SECTION_TAG, SIGNATURE_TAG};
+use std::fmt;
+use std::ops::Range;
+use xml::common::TextPosition;
+use xml::escape::escape_str_pcdata;
+use xml::name::OwnedName;
+use xml::reader::XmlEvent;
+
+pub const DEFAULT_PROLOG: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+
+/// Lossless model of a bank file.
+///
+/// Everything around the root element (XML declaration, comments, line endings) is
+/// kept verbatim, and whitespace between elements is kept as nodes, so serializing an
+/// unmodified document reproduces the file it was parsed from.
+#[derive(Debug, Clone)]
+pub struct BankDocument {
+    /// Raw text before the root element, including the XML declaration.
+    pub prolog: String,
+    pub root: Element,
+    /// Raw text after the root element closing tag.
+    pub epilog: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<XmlAttribute>,
+    pub children: Vec<Node>,
+    /// Written as `<Name/>` rather than `<Name></Name>` when it has no children.
+    pub self_closing: bool,
+    /// Start tag as it appeared in the source. Reused on output as long as it still
+    /// spells the element's current name and attributes.
+    pub raw_start_tag: Option<String>,
+    /// Where the start tag was found in the source, `None` for elements added by edits.
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlAttribute {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Element(Element),
+    /// Whitespace between elements, verbatim.
+    Whitespace(String),
+    Text(String),
+    CData(String),
+    Comment(String),
+    ProcessingInstruction { name: String, data: Option<String> },
+}
+
+/// Indentation unit and line ending used by a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formatting {
+    pub indent: String,
+    pub newline: String,
+}
+
+impl Default for Formatting {
+    /// The game's own layout: four spaces per level and `\n` line endings.
+    fn default() -> Self {
+        Formatting { indent: "    ".to_string(), newline: "\n".to_string() }
+    }
+}
+
+impl BankDocument {
+    /// Builds a new document holding `sections`, laid out the way the game writes
+    /// banks, with an empty `<Signature>` element at the end.
+    pub fn from_sections(sections: &[Section]) -> Self {
+        let formatting = Formatting::default();
+        let mut root = Element::new("Bank").with_attribute("version", "1");
+        for section in sections {
+            let mut section_element = Element::new(SECTION_TAG).with_attribute(NAME_ATTRIBUTE, &section.name);
+            for key in &section.keys {
+                let mut key_element = Element::new(KEY_TAG).with_attribute(NAME_ATTRIBUTE, &key.name);
+                for value in &key.values {
+                    key_element.insert_element(None, Element::from_value(value), &formatting, 2);
+                }
+                section_element.insert_element(None, key_element, &formatting, 1);
+            }
+            root.insert_element(None, section_element, &formatting, 0);
+        }
+        root.insert_element(None, Element::new(SIGNATURE_TAG).with_attribute("value", ""), &formatting, 0);
+        BankDocument {
+            prolog: DEFAULT_PROLOG.to_string(),
+            root,
+            epilog: formatting.newline,
+        }
+    }
+
+    /// Serializes the document back to XML.
+    pub fn to_xml(&self) -> String {
+        self.to_string()
+    }
+
+    /// Infers the formatting from the whitespace in front of the first child of the
+    /// root, so inserted elements line up with the existing ones.
+    pub fn formatting(&self) -> Formatting {
+        let Some(Node::Whitespace(whitespace)) = self.root.children.first() else {
+            return Formatting::default();
+        };
+        match whitespace.rfind('\n') {
+            Some(line_end) => Formatting {
+                indent: whitespace[line_end + 1..].to_string(),
+                newline: if whitespace.contains("\r\n") { "\r\n" } else { "\n" }.to_string(),
+            },
+            None => Formatting::default(),
+        }
+    }
+}
+
+impl Element {
+    pub fn new(name: impl Into<String>) -> Self {
+        Element {
+            name: name.into(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            self_closing: true,
+            raw_start_tag: None,
+            span: None,
+        }
+    }
+
+    /// The element holding a value of a key, e.g. `<Value int="7"/>`.
+    pub fn from_value(value: &ValueElement) -> Self {
+        let mut element = Element::new(&value.tag_name);
+        element.attributes = value
+            .attributes
+            .iter()
+            .map(|attribute| XmlAttribute { name: attribute.name().to_string(), value: attribute.value() })
+            .collect();
+        element
+    }
+
+    pub fn with_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push(XmlAttribute { name: name.into(), value: value.into() });
+        self
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.name == name)
+            .map(|attr| attr.value.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn elements_mut(&mut self) -> impl Iterator<Item = &mut Element> {
+        self.children.iter_mut().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Node index of the first child element called `tag` whose `name` attribute is `name`.
+    pub fn find_named_child(&self, tag: &str, name: &str) -> Option<usize> {
+        self.children.iter().position(|node| {
+            matches!(node, Node::Element(element) if element.name == tag && element.attribute(NAME_ATTRIBUTE) == Some(name))
+        })
+    }
+
+    /// Node index of the first child element called `tag`.
+    pub fn find_child(&self, tag: &str) -> Option<usize> {
+        self.children
+            .iter()
+            .position(|node| matches!(node, Node::Element(element) if element.name == tag))
+    }
+
+    pub fn child_element(&self, index: usize) -> Option<&Element> {
+        match self.children.get(index) {
+            Some(Node::Element(element)) => Some(element),
+            _ => None,
+        }
+    }
+
+    pub fn child_element_mut(&mut self, index: usize) -> Option<&mut Element> {
+        match self.children.get_mut(index) {
+            Some(Node::Element(element)) => Some(element),
+            _ => None,
+        }
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: impl Into<String>) {
+        match self.attributes.iter_mut().find(|attr| attr.name == name) {
+            Some(attr) => attr.value = value.into(),
+            None => self.attributes.push(XmlAttribute { name: name.to_string(), value: value.into() }),
+        }
+    }
+
+    /// Inserts `element` before the child node at `before`, or after the last child, on its
+    /// own indented line. `depth` is the nesting depth of `self`, the root being 0.
+    /// Returns the node index of the inserted element.
+    pub fn insert_element(
+        &mut self,
+        before: Option<usize>,
+        element: Element,
+        formatting: &Formatting,
+        depth: usize,
+    ) -> usize {
+        let child_indent = format!("{}{}", formatting.newline, formatting.indent.repeat(depth + 1));
+        match before {
+            Some(index) => {
+                self.children
+                    .splice(index..index, [Node::Element(element), Node::Whitespace(child_indent)]);
+                index
+            }
+            None => match self.children.last() {
+                Some(Node::Whitespace(_)) => {
+                    let index = self.children.len() - 1;
+                    self.children
+                        .splice(index..index, [Node::Whitespace(child_indent), Node::Element(element)]);
+                    index + 1
+                }
+                _ => {
+                    let closing_indent = format!("{}{}", formatting.newline, formatting.indent.repeat(depth));
+                    self.children.push(Node::Whitespace(child_indent));
+                    self.children.push(Node::Element(element));
+                    self.children.push(Node::Whitespace(closing_indent));
+                    self.children.len() - 2
+                }
+            },
+        }
+    }
+
+    /// Removes the child element at node index `index` along with the whitespace that
+    /// puts it on its own line.
+    pub fn remove_element(&mut self, index: usize) -> Option<Element> {
+        if !matches!(self.children.get(index), Some(Node::Element(_))) {
+            return None;
+        }
+        let Node::Element(element) = self.children.remove(index) else {
+            return None;
+        };
+        if index > 0 && matches!(self.children[index - 1], Node::Whitespace(_)) {
+            self.children.remove(index - 1);
+        }
+        if self.children.iter().all(|node| matches!(node, Node::Whitespace(_))) {
+            self.children.clear();
+            self.self_closing = true;
+        }
+        Some(element)
+    }
+
+    /// Where the attribute `name` was found in the source.
+    pub fn attribute_span(&self, name: &str) -> Option<Span> {
+        let tag_span = self.span?;
+        let (_, attributes) = scan_start_tag(self.raw_start_tag.as_deref()?)?;
+        let (_, range) = attributes.into_iter().find(|(attribute, _)| attribute.name == name)?;
+        let raw = self.raw_start_tag.as_deref()?;
+        let before = &raw[..range.start];
+        let (line, column) = match before.rfind('\n') {
+            Some(newline) => (
+                tag_span.line + before.matches('\n').count(),
+                before[newline + 1..].chars().count() + 1,
+            ),
+            None => (tag_span.line, tag_span.column + before.chars().count()),
+        };
+        Some(Span {
+            start: tag_span.start + range.start,
+            end: tag_span.start + range.end,
+            line,
+            column,
+        })
+    }
+
+    /// The start tag as it appeared in the source with the attribute `name` set to
+    /// `value`, and everything else, quotes and whitespace included, left as it was. An
+    /// attribute the tag does not have is added after the others. `None` for elements
+    /// not read from a source.
+    pub fn raw_start_tag_with(&self, name: &str, value: &str) -> Option<String> {
+        let raw = self.raw_start_tag.as_deref()?;
+        let (_, attributes) = scan_start_tag(raw)?;
+        match attributes.into_iter().find(|(attribute, _)| attribute.name == name) {
+            Some((_, range)) => {
+                // The range ends with the closing quote; only the value between the quotes changes.
+                let open = range.start + raw[range.clone()].find(['"', '\''])?;
+                let escaped = match &raw[open..=open] {
+                    "\"" => escape_attribute(value),
+                    _ => escape_attribute(value).replace('\'', "&apos;"),
+                };
+                Some(format!("{}{}{}", &raw[..=open], escaped, &raw[range.end - 1..]))
+            }
+            None => {
+                let close = if raw.ends_with("/>") { 2 } else { 1 };
+                let insert_at = raw[..raw.len() - close].trim_end().len();
+                Some(format!(
+                    "{} {}=\"{}\"{}",
+                    &raw[..insert_at],
+                    name,
+                    escape_attribute(value),
+                    &raw[insert_at..]
+                ))
+            }
+        }
+    }
+
+    fn raw_start_tag_is_current(&self, raw: &str) -> bool {
+        match parse_start_tag(raw) {
+            Some((name, attributes)) => name == self.name && attributes == self.attributes,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for BankDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.prolog, self.root, self.epilog)
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let closes_itself = self.children.is_empty() && self.self_closing;
+        match self.raw_start_tag.as_deref() {
+            Some(raw) if raw.ends_with("/>") == closes_itself && self.raw_start_tag_is_current(raw) => {
+                write!(f, "{}", raw)?;
+            }
+            _ => {
+                write!(f, "<{}", self.name)?;
+                for attr in &self.attributes {
+                    write!(f, " {}=\"{}\"", attr.name, escape_attribute(&attr.value))?;
+                }
+                write!(f, "{}", if closes_itself { "/>" } else { ">" })?;
+            }
+        }
+        if closes_itself {
+            return Ok(());
+        }
+        for child in &self.children {
+            write!(f, "{}", child)?;
+        }
+        write!(f, "</{}>", self.name)
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Element(element) => write!(f, "{}", element),
+            Node::Whitespace(text) => write!(f, "{}", text),
+            Node::Text(text) => write!(f, "{}", escape_str_pcdata(text)),
+            Node::CData(text) => write!(f, "<![CDATA[{}]]>", text),
+            Node::Comment(text) => write!(f, "<!--{}-->", text),
+            Node::ProcessingInstruction { name, data: Some(data) } => write!(f, "<?{} {}?>", name, data),
+            Node::ProcessingInstruction { name, data: None } => write!(f, "<?{}?>", name),
+        }
+    }
+}
+
+/// Escapes an attribute value for a double-quoted attribute.
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(raw: &str) -> Option<String> {
+    let mut value = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        value.push_str(&rest[..amp]);
+        let semicolon = rest[amp..].find(';')? + amp;
+        let entity = &rest[amp + 1..semicolon];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        };
+        value.push(c);
+        rest = &rest[semicolon + 1..];
+    }
+    value.push_str(rest);
+    Some(value)
+}
+
+/// Byte offset just past the `>` that closes the tag starting at `start`.
+/// Quoted attribute values may contain `>` and are skipped.
+pub(crate) fn tag_end(source: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (index, c) in source[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return start + index + 1,
+            _ => {}
+        }
+    }
+    source.len()
+}
+
+/// An attribute of a raw start tag, with the byte range it covers in the tag.
+type ScannedAttribute = (XmlAttribute, Range<usize>);
+
+/// Splits a raw start tag into its name and unescaped attributes.
+fn scan_start_tag(raw: &str) -> Option<(String, Vec<ScannedAttribute>)> {
+    let inner = raw.strip_prefix('<')?;
+    let inner = inner.strip_suffix("/>").or_else(|| inner.strip_suffix('>'))?;
+    // `rest` is always a suffix of `inner`, which starts one byte into `raw`.
+    let offset_in_raw = |rest: &str| 1 + inner.len() - rest.len();
+    let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+    let name = inner[..name_end].to_string();
+
+    let mut attributes = Vec::new();
+    let mut rest = inner[name_end..].trim_start();
+    while !rest.is_empty() {
+        let start = offset_in_raw(rest);
+        let equals = rest.find('=')?;
+        let attr_name = rest[..equals].trim_end();
+        let after_equals = rest[equals + 1..].trim_start();
+        let quote = after_equals.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value_end = after_equals[1..].find(quote)? + 1;
+        let attribute = XmlAttribute {
+            name: attr_name.to_string(),
+            value: unescape(&after_equals[1..value_end])?,
+        };
+        rest = &after_equals[value_end + 1..];
+        attributes.push((attribute, start..offset_in_raw(rest)));
+        rest = rest.trim_start();
+    }
+    Some((name, attributes))
+}
+
+fn parse_start_tag(raw: &str) -> Option<(String, Vec<XmlAttribute>)> {
+    let (name, attributes) = scan_start_tag(raw)?;
+    Some((name, attributes.into_iter().map(|(attribute, _)| attribute).collect()))
+}
+
+pub(crate) fn qualified_name(name: &OwnedName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+/// Builds a `BankDocument` from the reader events of a source text.
+pub(crate) struct DocumentBuilder<'a> {
+    source: &'a str,
+    source_map: SourceMap,
+    prolog: Option<String>,
+    open_elements: Vec<Element>,
+    root: Option<(Element, usize)>,
+    /// Start offset of a whitespace run whose end is the next event.
+    pending_whitespace: Option<usize>,
+}
+
+impl<'a> DocumentBuilder<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        DocumentBuilder {
+            source,
+            source_map: SourceMap::new(source),
+            prolog: None,
+            open_elements: Vec::new(),
+            root: None,
+            pending_whitespace: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: &XmlEvent, position: TextPosition) {
+        let offset = self.source_map.offset(self.source, position);
+        if let Some(start) = self.pending_whitespace.take() {
+            self.push_node(Node::Whitespace(self.source[start..offset.max(start)].to_string()));
+        }
+
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                // The reader reports the first element of a document without an XML
+                // declaration past its `<`, so always anchor on the actual tag start.
+                let start = if self.source[offset..].starts_with('<') {
+                    offset
+                } else {
+                    self.source[..offset].rfind('<').unwrap_or(offset)
+                };
+                if self.prolog.is_none() {
+                    self.prolog = Some(self.source[..start].to_string());
+                }
+                let end = tag_end(self.source, start);
+                let element = Element {
+                    name: qualified_name(name),
+                    attributes: attributes
+                        .iter()
+                        .map(|attr| XmlAttribute { name: qualified_name(&attr.name), value: attr.value.clone() })
+                        .collect(),
+                    children: Vec::new(),
+                    self_closing: self.source[..end].ends_with("/>"),
+                    raw_start_tag: Some(self.source[start..end].to_string()),
+                    span: Some(self.source_map.span(self.source, start, end)),
+                };
+                self.open_elements.push(element);
+            }
+            XmlEvent::EndElement { .. } => {
+                if let Some(element) = self.open_elements.pop() {
+                    if self.open_elements.is_empty() {
+                        // Either the closing tag, or the start tag of a self-closing root.
+                        self.root = Some((element, tag_end(self.source, offset)));
+                    } else {
+                        self.push_node(Node::Element(element));
+                    }
+                }
+            }
+            XmlEvent::Whitespace(_) => self.pending_whitespace = Some(offset),
+            XmlEvent::Characters(text) => self.push_node(Node::Text(text.clone())),
+            XmlEvent::CData(text) => self.push_node(Node::CData(text.clone())),
+            XmlEvent::Comment(text) => self.push_node(Node::Comment(text.clone())),
+            XmlEvent::ProcessingInstruction { name, data } => {
+                self.push_node(Node::ProcessingInstruction { name: name.clone(), data: data.clone() })
+            }
+            XmlEvent::StartDocument { .. } | XmlEvent::EndDocument => {}
+        }
+    }
+
+    /// Adds a node to the innermost open element. Nodes outside the root element are
+    /// already part of the raw prolog or epilog.
+    fn push_node(&mut self, node: Node) {
+        if let Some(parent) = self.open_elements.last_mut() {
+            parent.children.push(node);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Result<BankDocument, BankParserError> {
+        let (root, root_end) = self.root.ok_or(BankParserError::MissingRootElement)?;
+        Ok(BankDocument {
+            prolog: self.prolog.unwrap_or_default(),
+            root,
+            epilog: self.source[root_end..].to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Element;
+    use crate::bank_parser::BankParser;
+    use crate::signing_context::SigningContext;
+
+    fn assert_round_trips(source: &str) {
+        let context = SigningContext::new("Bank", "1-S2-1-1234567".parse().unwrap(), "2-S2-1-7654321".parse().unwrap());
+        let bank = BankParser::from_str(source, context).unwrap();
+        assert_eq!(bank.to_xml(), source);
+    }
+
+    #[test]
+    fn keeps_comments_and_processing_instructions() {
+        assert_round_trips(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- written by hand -->
+<?editor cursor="12"?>
+<Bank version="1">
+    <!-- stats -->
+    <Section name="Stats">
+        <Key name="Gold"><?keep?><Value int="7"/><!-- was 6 --></Key>
+    </Section>
+    <Signature value="ABC"/>
+</Bank>
+<!-- trailing -->
+"#,
+        );
+    }
+
+    #[test]
+    fn keeps_line_endings_and_bom() {
+        assert_round_trips(concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n",
+            "<Bank version=\"1\">\r\n",
+            "\t<Section name=\"Stats\">\r\n",
+            "\t\t<Key name=\"Gold\"><Value int=\"7\"/></Key>\r\n",
+            "\t</Section>\r\n",
+            "</Bank>\r\n",
+        ));
+        assert_round_trips(concat!(
+            "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+            "<Bank version=\"1\"><Section name=\"Stats\"/></Bank>\n",
+        ));
+        assert_round_trips("<Bank version=\"1\">\n<Section name=\"Stats\"/>\n</Bank>");
+    }
+
+    #[test]
+    fn keeps_self_closing_and_empty_elements() {
+        assert_round_trips(
+            r#"<Bank version="1">
+    <Section name="Empty"></Section>
+    <Section name="Closed"/>
+    <Section name="Stats">
+        <Key name="Empty"></Key>
+        <Key name="Closed" />
+        <Key name="Gold"><Value int="7"></Value></Key>
+    </Section>
+    <Signature value="ABC" />
+</Bank>"#,
+        );
+    }
+
+    #[test]
+    fn keeps_quoting_and_attribute_spacing() {
+        assert_round_trips(
+            r#"<?xml version='1.0' encoding="utf-8"?>
+<Bank version='1'>
+    <Section  name = 'Stats' >
+        <Key name="Title"><Value string='say "hi"'/></Key>
+        <Key name='Quote'><Value string="it's &amp; &lt;ok&gt;"/></Key>
+        <Key
+            name="Wrapped"><Value
+            int="7"/></Key>
+    </Section>
+    <Signature value='ABC'/>
+</Bank>"#,
+        );
+    }
+
+    #[test]
+    fn sets_a_value_keeping_the_rest_of_the_raw_start_tag() {
+        let element = |raw: &str| Element { raw_start_tag: Some(raw.to_string()), ..Element::new("Signature") };
+        let set = |raw: &str, value: &str| element(raw).raw_start_tag_with("value", value).unwrap();
+        assert_eq!(set("<Signature value = 'ABC' />", "DEF"), "<Signature value = 'DEF' />");
+        assert_eq!(set("<Signature\n    value=\"\"/>", "DEF"), "<Signature\n    value=\"DEF\"/>");
+        assert_eq!(set("<Signature value='ABC'/>", "it's"), "<Signature value='it&apos;s'/>");
+        assert_eq!(set(r#"<Signature value="ABC"/>"#, r#"say "hi""#), r#"<Signature value="say &quot;hi&quot;"/>"#);
+        assert_eq!(set("<Signature other='1'  />", "DEF"), r#"<Signature other='1' value="DEF"  />"#);
+        assert_eq!(Element::new("Signature").raw_start_tag_with("value", "DEF"), None);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::document::{Element, Formatting, XmlAttribute};
+use crate::bank_parser::fixed::Fixed;
+use crate::bank_parser::flag::Flag;
+use crate::bank_parser::key::Key;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::{
+    BankParser, BankParserError, KEY_TAG, NAME_ATTRIBUTE, SECTION_TAG, SIGNATURE_TAG, VALUE_TAG,
+};
+use crate::AppResult;
+
+
+/// Typed access to `Section/Key` values.
+///
+/// Reads go through the parsed `sections`; writes edit the document in place, keeping
+/// the rest of the file as it was, and recompute `signature`. A write that fails leaves
+/// the bank as it was.
+impl BankParser {
+    pub fn section(&self, section: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == section)
+    }
+
+    pub fn key(&self, section: &str, key: &str) -> Option<&Key> {
+        self.section(section)?.key(key)
+    }
+
+    pub fn value(&self, section: &str, key: &str) -> Option<&Attribute> {
+        self.key(section, key)?.value()
+    }
+
+    pub fn get_int(&self, section: &str, key: &str) -> Option<i32> {
+        self.value(section, key)?.as_int()
+    }
+
+    pub fn get_fixed(&self, section: &str, key: &str) -> Option<Fixed> {
+        self.value(section, key)?.as_fixed().copied()
+    }
+
+    pub fn get_flag(&self, section: &str, key: &str) -> Option<bool> {
+        self.value(section, key)?.as_flag()
+    }
+
+    pub fn get_string(&self, section: &str, key: &str) -> Option<&str> {
+        self.value(section, key)?.as_string()
+    }
+
+    pub fn get_text(&self, section: &str, key: &str) -> Option<&str> {
+        self.value(section, key)?.as_text()
+    }
+
+    pub fn set_int(&mut self, section: &str, key: &str, value: i32) -> AppResult<()> {
+        self.set_value(section, key, Attribute::Int(value))
+    }
+
+    pub fn set_fixed(&mut self, section: &str, key: &str, value: Fixed) -> AppResult<()> {
+        self.set_value(section, key, Attribute::Fixed(value, None))
+    }
+
+    pub fn set_flag(&mut self, section: &str, key: &str, value: bool) -> AppResult<()> {
+        self.set_value(section, key, Attribute::Flag(Flag::new(value)))
+    }
+
+    pub fn set_string(&mut self, section: &str, key: &str, value: &str) -> AppResult<()> {
+        self.set_value(section, key, Attribute::String(value.to_string()))
+    }
+
+    pub fn set_text(&mut self, section: &str, key: &str, value: &str) -> AppResult<()> {
+        self.set_value(section, key, Attribute::Text(value.to_string()))
+    }
+
+    /// Sets the `<Value>` of `section/key`, creating the section and key if needed.
+    pub fn set_value(&mut self, section: &str, key: &str, value: Attribute) -> AppResult<()> {
+        self.edit_document(|root, formatting| {
+            let section_index = match root.find_named_child(SECTION_TAG, section) {
+                Some(index) => index,
+                None => {
+                    // New sections go in front of the signature, where the game keeps them.
+                    let before = root.find_child(SIGNATURE_TAG);
+                    let element = Element::new(SECTION_TAG).with_attribute(NAME_ATTRIBUTE, section);
+                    root.insert_element(before, element, formatting, 0)
+                }
+            };
+            let section_element = root.child_element_mut(section_index).expect("section index points at an element");
+
+            let key_index = match section_element.find_named_child(KEY_TAG, key) {
+                Some(index) => index,
+                None => {
+                    let element = Element::new(KEY_TAG).with_attribute(NAME_ATTRIBUTE, key);
+                    section_element.insert_element(None, element, formatting, 1)
+                }
+            };
+            let key_element = section_element.child_element_mut(key_index).expect("key index points at an element");
+
+            let value_index = match key_element.find_child(VALUE_TAG) {
+                Some(index) => index,
+                None => key_element.insert_element(None, Element::new(VALUE_TAG), formatting, 2),
+            };
+            let value_element = key_element.child_element_mut(value_index).expect("value index points at an element");
+            value_element.attributes = vec![XmlAttribute { name: value.name().to_string(), value: value.value() }];
+            Ok(())
+        })
+    }
+
+    /// Adds an empty section unless one called `section` already exists.
+    pub fn ensure_section(&mut self, section: &str) -> AppResult<()> {
+        if self.document.root.find_named_child(SECTION_TAG, section).is_some() {
+            return Ok(());
+        }
+        self.edit_document(|root, formatting| {
+            let before = root.find_child(SIGNATURE_TAG);
+            let element = Element::new(SECTION_TAG).with_attribute(NAME_ATTRIBUTE, section);
+            root.insert_element(before, element, formatting, 0);
+            Ok(())
+        })
+    }
+
+    pub fn remove_section(&mut self, section: &str) -> AppResult<()> {
+        self.edit_document(|root, _| {
+            let index = root
+                .find_named_child(SECTION_TAG, section)
+                .ok_or_else(|| BankParserError::SectionNotFound(section.to_string()))?;
+            root.remove_element(index);
+            Ok(())
+        })
+    }
+
+    pub fn remove_key(&mut self, section: &str, key: &str) -> AppResult<()> {
+        self.edit_document(|root, _| {
+            let section_element = section_element_mut(root, section)?;
+            let index = section_element
+                .find_named_child(KEY_TAG, key)
+                .ok_or_else(|| key_not_found(section, key))?;
+            section_element.remove_element(index);
+            Ok(())
+        })
+    }
+
+    pub fn rename_section(&mut self, section: &str, new_name: &str) -> AppResult<()> {
+        self.edit_document(|root, _| {
+            if root.find_named_child(SECTION_TAG, new_name).is_some() {
+                return Err(BankParserError::SectionAlreadyExists(new_name.to_string()));
+            }
+            section_element_mut(root, section)?.set_attribute(NAME_ATTRIBUTE, new_name);
+            Ok(())
+        })
+    }
+
+    pub fn rename_key(&mut self, section: &str, key: &str, new_name: &str) -> AppResult<()> {
+        self.edit_document(|root, _| {
+            let section_element = section_element_mut(root, section)?;
+            if section_element.find_named_child(KEY_TAG, new_name).is_some() {
+                return Err(BankParserError::KeyAlreadyExists {
+                    section: section.to_string(),
+                    key: new_name.to_string(),
+                });
+            }
+            let index = section_element
+                .find_named_child(KEY_TAG, key)
+                .ok_or_else(|| key_not_found(section, key))?;
+            section_element
+                .child_element_mut(index)
+                .expect("key index points at an element")
+                .set_attribute(NAME_ATTRIBUTE, new_name);
+            Ok(())
+        })
+    }
+
+    /// Applies `edit` to a copy of the document's root, which replaces the root only if
+    /// the edited bank reads back, so a failed edit leaves the bank as it was.
+    pub(super) fn edit_document(
+        &mut self,
+        edit: impl FnOnce(&mut Element, &Formatting) -> Result<(), BankParserError>,
+    ) -> AppResult<()> {
+        let formatting = self.document.formatting();
+        let mut root = self.document.root.clone();
+        edit(&mut root, &formatting)?;
+        let previous = std::mem::replace(&mut self.document.root, root);
+        // `refresh` only updates the bank once the document has been read.
+        if let Err(err) = self.refresh() {
+            self.document.root = previous;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
+
+fn section_element_mut<'a>(root: &'a mut Element, section: &str) -> Result<&'a mut Element, BankParserError> {
+    root.find_named_child(SECTION_TAG, section)
+        .and_then(|index| root.child_element_mut(index))
+        .ok_or_else(|| BankParserError::SectionNotFound(section.to_string()))
+}
+
+fn key_not_found(section: &str, key: &str) -> BankParserError {
+    BankParserError::KeyNotFound {
+        section: section.to_string(),
+        key: key.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_context::SigningContext;
+
+    const SOURCE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Bank version="1">
+    <Section name="Stats">
+        <Key name="Gold"><Value int="7"/></Key>
+        <Key name="Rate"><Value fixed="1.5"/></Key>
+    </Section>
+    <Section name="Options">
+        <Key name="Music"><Value flag="1"/></Key>
+        <Key name="Name"><Value string="Raynor"/></Key>
+        <Key name="Note"><Value text="hello"/></Key>
+    </Section>
+    <Signature value="ABC"/>
+</Bank>
+"#;
+
+    fn bank() -> BankParser {
+        let context = SigningContext::new("Bank", "1-S2-1-1234567".parse().unwrap(), "2-S2-1-7654321".parse().unwrap());
+        BankParser::from_str(SOURCE, context).unwrap()
+    }
+
+    /// The bank as it would be read from what it writes.
+    fn reread(bank: &BankParser) -> BankParser {
+        BankParser::from_str(&bank.to_xml(), bank.context.clone()).unwrap()
+    }
+
+    #[test]
+    fn reads_typed_values() {
+        let bank = bank();
+        assert_eq!(bank.get_int("Stats", "Gold"), Some(7));
+        assert_eq!(bank.get_fixed("Stats", "Rate"), Some(Fixed::new(1.5)));
+        assert_eq!(bank.get_flag("Options", "Music"), Some(true));
+        assert_eq!(bank.get_string("Options", "Name"), Some("Raynor"));
+        assert_eq!(bank.get_text("Options", "Note"), Some("hello"));
+        assert_eq!(bank.get_int("Stats", "Rate"), None);
+        assert_eq!(bank.get_int("Stats", "Missing"), None);
+        assert_eq!(bank.get_int("Missing", "Gold"), None);
+    }
+
+    #[test]
+    fn sets_values_in_place() {
+        let mut bank = bank();
+        let signature = bank.signature.clone();
+        bank.set_int("Stats", "Gold", 8).unwrap();
+        assert_eq!(bank.get_int("Stats", "Gold"), Some(8));
+        assert_ne!(bank.signature, signature);
+        assert_eq!(bank.to_xml(), SOURCE.replace(r#"int="7""#, r#"int="8""#));
+
+        bank.set_fixed("Stats", "Rate", Fixed::new(0.25)).unwrap();
+        bank.set_flag("Options", "Music", false).unwrap();
+        bank.set_string("Options", "Name", "Kerrigan").unwrap();
+        bank.set_text("Options", "Note", "a & b").unwrap();
+        let reread = reread(&bank);
+        assert_eq!(reread.get_fixed("Stats", "Rate"), Some(Fixed::new(0.25)));
+        assert_eq!(reread.get_flag("Options", "Music"), Some(false));
+        assert_eq!(reread.get_string("Options", "Name"), Some("Kerrigan"));
+        assert_eq!(reread.get_text("Options", "Note"), Some("a & b"));
+        assert_eq!(reread.signature, bank.signature);
+    }
+
+    #[test]
+    fn sets_new_sections_and_keys_before_the_signature() {
+        let mut bank = bank();
+        bank.set_int("Stats", "Lives", 3).unwrap();
+        bank.set_int("New", "Key", 1).unwrap();
+        let xml = bank.to_xml();
+        assert!(xml.find(r#"<Section name="New">"#).unwrap() < xml.find("<Signature").unwrap());
+        let reread = reread(&bank);
+        assert_eq!(reread.get_int("Stats", "Lives"), Some(3));
+        assert_eq!(reread.get_int("New", "Key"), Some(1));
+        assert_eq!(reread.signature, bank.signature);
+    }
+
+    #[test]
+    fn removes_and_renames() {
+        let mut bank = bank();
+        bank.remove_key("Stats", "Rate").unwrap();
+        assert!(bank.key("Stats", "Rate").is_none());
+        bank.rename_key("Stats", "Gold", "Coins").unwrap();
+        assert_eq!(bank.get_int("Stats", "Coins"), Some(7));
+        bank.rename_section("Stats", "Purse").unwrap();
+        assert_eq!(bank.get_int("Purse", "Coins"), Some(7));
+        bank.remove_section("Options").unwrap();
+        assert!(bank.section("Options").is_none());
+        bank.ensure_section("Empty").unwrap();
+        bank.ensure_section("Empty").unwrap();
+        assert_eq!(bank.to_xml().matches(r#"<Section name="Empty"/>"#).count(), 1);
+        assert_eq!(reread(&bank).signature, bank.signature);
+    }
+
+    #[test]
+    fn rejects_missing_and_existing_names() {
+        let mut bank = bank();
+        let err = |result: AppResult<()>| match result.unwrap_err() {
+            crate::AppError::BankParseError(err) => err.to_string(),
+            err => panic!("{}", err),
+        };
+        assert_eq!(err(bank.remove_section("Missing")), "Section 'Missing' not found");
+        assert_eq!(err(bank.remove_key("Stats", "Missing")), "Key 'Stats/Missing' not found");
+        assert_eq!(err(bank.rename_section("Stats", "Options")), "Section 'Options' already exists");
+        assert_eq!(err(bank.rename_key("Stats", "Gold", "Rate")), "Key 'Stats/Rate' already exists");
+        assert_eq!(bank.to_xml(), SOURCE);
+    }
+
+    #[test]
+    fn failed_edits_leave_the_bank_unchanged() {
+        let mut bank = bank();
+        let signature = bank.signature.clone();
+        let invalid = Attribute::Custom("int".to_string(), "x".to_string());
+        assert!(bank.set_value("Stats", "Gold", invalid.clone()).is_err());
+        assert!(bank.set_value("New", "Key", invalid).is_err());
+        assert_eq!(bank.to_xml(), SOURCE);
+        assert_eq!(bank.get_int("Stats", "Gold"), Some(7));
+        assert!(bank.section("New").is_none());
+        assert_eq!(bank.signature, signature);
+    }
+}
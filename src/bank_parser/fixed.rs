@@ -1,37 +1,270 @@
 use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
-pub struct Fixed(pub f32);
+/// Galaxy `fixed`: a signed 20.12 fixed-point number, stored as its raw `i32`.
+///
+/// Banks store fixed values as decimal text. Parsing rounds the text to the nearest
+/// 1/4096, and formatting writes the shortest decimal (at most four fractional digits)
+/// that parses back to the same raw value. Whole values are written without a decimal
+/// point. Text in a bank need not be in that form, which is why `Attribute::Fixed`
+/// keeps it for signing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FixedError {
+    #[error("Invalid fixed value '{0}'")]
+    Invalid(String),
+    #[error("Fixed value '{0}' is out of range")]
+    OutOfRange(String),
+}
+
+const MAX_FRACTION_DIGITS: u32 = 4;
+
 impl Fixed {
-    pub fn new(value: f32) -> Self {
-        Fixed(value)
+    pub const FRACTIONAL_BITS: u32 = 12;
+    pub const SCALE: i32 = 1 << Self::FRACTIONAL_BITS;
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(Self::SCALE);
+    pub const MIN: Fixed = Fixed(i32::MIN);
+    pub const MAX: Fixed = Fixed(i32::MAX);
+
+    /// Converts a float, rounding to the nearest representable value and saturating
+    /// at the ends of the range.
+    pub fn new(value: f64) -> Self {
+        // `as` saturates on overflow and maps NaN to 0.
+        Fixed((value * Self::SCALE as f64).round() as i32)
+    }
+
+    pub const fn from_raw(raw: i32) -> Self {
+        Fixed(raw)
+    }
+
+    pub const fn raw(self) -> i32 {
+        self.0
     }
+
+    /// The whole number `value`, if it fits the 20 integer bits.
+    pub fn from_int(value: i32) -> Option<Self> {
+        value.checked_mul(Self::SCALE).map(Fixed)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    pub fn checked_add(self, rhs: Fixed) -> Option<Fixed> {
+        self.0.checked_add(rhs.0).map(Fixed)
+    }
+
+    pub fn checked_sub(self, rhs: Fixed) -> Option<Fixed> {
+        self.0.checked_sub(rhs.0).map(Fixed)
+    }
+
+    /// Multiplies, truncating the product toward zero to the nearest 1/4096.
+    pub fn checked_mul(self, rhs: Fixed) -> Option<Fixed> {
+        let product = i64::from(self.0) * i64::from(rhs.0) / i64::from(Self::SCALE);
+        i32::try_from(product).ok().map(Fixed)
+    }
+
+    /// Divides, truncating the quotient toward zero. `None` on division by zero.
+    pub fn checked_div(self, rhs: Fixed) -> Option<Fixed> {
+        if rhs.0 == 0 {
+            return None;
+        }
+        let quotient = (i64::from(self.0) << Self::FRACTIONAL_BITS) / i64::from(rhs.0);
+        i32::try_from(quotient).ok().map(Fixed)
+    }
+
+    pub fn saturating_add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+
+    pub fn saturating_sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+
+    pub fn saturating_mul(self, rhs: Fixed) -> Fixed {
+        self.checked_mul(rhs).unwrap_or(if (self.0 < 0) == (rhs.0 < 0) { Fixed::MAX } else { Fixed::MIN })
+    }
+}
+
+/// `n / d` rounded half away from zero, for non-negative operands.
+fn div_round(n: u128, d: u128) -> u128 {
+    (n + d / 2) / d
 }
 
-impl std::fmt::Display for Fixed {
+impl fmt::Display for Fixed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.0.fract().abs() < f32::EPSILON {
-            // Format as integer if effectively whole
-            write!(f, "{}", self.0 as i32)
-        } else {
-            // Default float formatting
-            write!(f, "{}", self.0)
+        let magnitude = u128::from(self.0.unsigned_abs());
+        let scale = Self::SCALE as u128;
+        let sign = if self.0 < 0 { "-" } else { "" };
+
+        for digits in 0..=MAX_FRACTION_DIGITS {
+            let power = 10u128.pow(digits);
+            let decimal = div_round(magnitude * power, scale);
+            // Four digits always round-trip: half a step of 0.0001 is below 1/8192.
+            if digits < MAX_FRACTION_DIGITS && div_round(decimal * scale, power) != magnitude {
+                continue;
+            }
+            let whole = decimal / power;
+            let fraction = decimal % power;
+            if fraction == 0 {
+                return write!(f, "{}{}", sign, whole);
+            }
+            let fraction = format!("{:0width$}", fraction, width = digits as usize);
+            return write!(f, "{}{}.{}", sign, whole, fraction.trim_end_matches('0'));
         }
+        unreachable!("four fractional digits always identify a fixed value")
     }
 }
 
 impl FromStr for Fixed {
-    type Err = std::num::ParseFloatError; // Use standard error type
+    type Err = FixedError;
 
+    /// Parses a plain decimal such as `-12.5`, rounding to the nearest 1/4096.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Return Result, let caller decide on default if needed
-        s.parse().map(Fixed)
+        let invalid = || FixedError::Invalid(s.to_string());
+        let (negative, unsigned) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+            return Err(invalid());
+        }
+
+        let out_of_range = || FixedError::OutOfRange(s.to_string());
+        let whole: u128 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| out_of_range())? };
+        // Digits past the 18th cannot move the result by a 4096th.
+        let fraction = &fraction[..fraction.len().min(18)];
+        let power = 10u128.pow(fraction.len() as u32);
+        let fraction: u128 = if fraction.is_empty() { 0 } else { fraction.parse().map_err(|_| invalid())? };
+
+        let scale = Self::SCALE as u128;
+        let magnitude = whole
+            .checked_mul(scale)
+            .and_then(|w| w.checked_add(div_round(fraction * scale, power)))
+            .ok_or_else(out_of_range)?;
+        let raw = if negative {
+            i64::try_from(magnitude).map(|m| -m).map_err(|_| out_of_range())?
+        } else {
+            i64::try_from(magnitude).map_err(|_| out_of_range())?
+        };
+        i32::try_from(raw).map(Fixed).map_err(|_| out_of_range())
     }
 }
 
 impl From<&str> for Fixed {
     fn from(value: &str) -> Self {
-        Fixed::from_str(value).unwrap_or(Fixed(0.0))
+        Fixed::from_str(value).unwrap_or(Fixed::ZERO)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    /// Saturates at the ends of the range.
+    fn add(self, rhs: Fixed) -> Fixed {
+        self.saturating_add(rhs)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    /// Saturates at the ends of the range.
+    fn sub(self, rhs: Fixed) -> Fixed {
+        self.saturating_sub(rhs)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    /// Saturates at the ends of the range.
+    fn mul(self, rhs: Fixed) -> Fixed {
+        self.saturating_mul(rhs)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    /// Saturates at the ends of the range.
+    ///
+    /// # Panics
+    /// Panics if `rhs` is zero, like integer division.
+    fn div(self, rhs: Fixed) -> Fixed {
+        assert!(rhs.0 != 0, "attempt to divide a fixed value by zero");
+        self.checked_div(rhs)
+            .unwrap_or(if (self.0 < 0) == (rhs.0 < 0) { Fixed::MAX } else { Fixed::MIN })
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    /// Saturates at the ends of the range.
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_the_shortest_text_that_parses_back() {
+        let cases = [
+            (0, "0"),
+            (4096, "1"),
+            (-4096, "-1"),
+            (2048, "0.5"),
+            (1024, "0.25"),
+            (6144, "1.5"),
+            (-13312, "-3.25"),
+            (1, "0.0002"),
+            (409, "0.0999"),
+            (410, "0.1"),
+            (i32::MAX, "524287.9998"),
+            (i32::MIN, "-524288"),
+        ];
+        for (raw, text) in cases {
+            assert_eq!(Fixed::from_raw(raw).to_string(), text, "raw {}", raw);
+            assert_eq!(text.parse::<Fixed>(), Ok(Fixed::from_raw(raw)), "text {}", text);
+        }
+    }
+
+    #[test]
+    fn parses_to_the_nearest_step() {
+        assert_eq!("0.1".parse::<Fixed>().map(Fixed::raw), Ok(410));
+        assert_eq!("0.2999".parse::<Fixed>().map(Fixed::raw), Ok(1228));
+        assert_eq!("0.00012".parse::<Fixed>().map(Fixed::raw), Ok(0));
+        assert_eq!("+2".parse::<Fixed>().map(Fixed::raw), Ok(8192));
+        assert_eq!(".5".parse::<Fixed>().map(Fixed::raw), Ok(2048));
+        assert_eq!("1.50".parse::<Fixed>().map(Fixed::raw), Ok(6144));
+    }
+
+    #[test]
+    fn rejects_malformed_and_out_of_range_text() {
+        for text in ["", "-", ".", "1e3", "0x10", "1.2.3", " 1", "abc"] {
+            assert!(matches!(text.parse::<Fixed>(), Err(FixedError::Invalid(_))), "text {:?}", text);
+        }
+        for text in ["524288", "-524289", "99999999999999999999999"] {
+            assert!(matches!(text.parse::<Fixed>(), Err(FixedError::OutOfRange(_))), "text {:?}", text);
+        }
+    }
+
+    #[test]
+    fn arithmetic_saturates() {
+        assert_eq!(Fixed::MAX + Fixed::ONE, Fixed::MAX);
+        assert_eq!(Fixed::MIN - Fixed::ONE, Fixed::MIN);
+        assert_eq!(Fixed::from_raw(6144) * Fixed::from_raw(8192), Fixed::from_raw(12288));
+        assert_eq!(Fixed::ONE.checked_div(Fixed::ZERO), None);
     }
 }
@@ -0,0 +1,98 @@
+use crate::bank_parser::collector::SectionCollector;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::{BankParser, ParseMode};
+use crate::signing_context::SigningContext;
+use crate::AppResult;
+use serde::{Deserialize, Serialize};
+
+/// The interchange form of a bank: its sections, keys and typed values, sorted the
+/// way they are signed. Sections and keys without values are kept, though they are
+/// not signed. Formatting and the signature are not part of it; importing lays the
+/// bank out the way the game does and signs it.
+///
+/// ```json
+/// {
+///   "sections": [
+///     {
+///       "name": "Stats",
+///       "keys": [
+///         {
+///           "name": "Gold",
+///           "values": [{ "tag_name": "Value", "attributes": [{ "name": "int", "value": 100 }] }]
+///         }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankContents {
+    pub sections: Vec<Section>,
+}
+
+impl BankParser {
+    pub fn contents(&self) -> BankContents {
+        // The document was read without errors already, so collecting it again only
+        // fails if it was since edited into a shape no bank has.
+        let sections = SectionCollector::collect_all(&self.document.root, ParseMode::Lenient)
+            .map(|collected| collected.sections)
+            .unwrap_or_else(|_| self.sections.clone());
+        BankContents { sections }
+    }
+
+    /// The bank's contents as pretty-printed JSON.
+    pub fn to_json(&self) -> AppResult<String> {
+        Ok(serde_json::to_string_pretty(&self.contents())?)
+    }
+
+    /// Builds a new, signed bank from JSON written by `to_json`.
+    pub fn from_json(json: &str, context: SigningContext) -> AppResult<Self> {
+        let contents: BankContents = serde_json::from_str(json)?;
+        Self::from_sections(&contents.sections, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> SigningContext {
+        SigningContext::new("Bank", "1-S2-1-1234567".parse().unwrap(), "2-S2-1-7654321".parse().unwrap())
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let source = r#"<Bank version="1">
+    <Section name="Stats">
+        <Key name="Empty"/>
+        <Key name="Gold"><Value int="7"/></Key>
+        <Key name="Rate"><Value fixed="0.2999"/></Key>
+        <Key name="Title"><Value string="Marine" text="left out"/><Extra flag="1"/></Key>
+    </Section>
+    <Section name="Empty"/>
+    <Section name="Options"><Key name="Empty"></Key></Section>
+    <Signature value=""/>
+</Bank>"#;
+        let bank = BankParser::from_str(source, context()).unwrap();
+        let imported = BankParser::from_json(&bank.to_json().unwrap(), context()).unwrap();
+        assert_eq!(imported.contents().sections, bank.contents().sections);
+        assert_eq!(imported.sections, bank.sections);
+        assert_eq!(imported.signature, bank.signature);
+        assert_eq!(imported.current_signature.as_deref(), Some(bank.signature.as_str()));
+
+        let names = |bank: &BankParser| -> Vec<(String, Vec<String>)> {
+            let sections = bank.contents().sections.into_iter();
+            sections.map(|s| (s.name, s.keys.into_iter().map(|k| k.name).collect())).collect()
+        };
+        let expected = [
+            ("Empty", vec![]),
+            ("Options", vec!["Empty"]),
+            ("Stats", vec!["Empty", "Gold", "Rate", "Title"]),
+        ];
+        let expected: Vec<(String, Vec<String>)> = expected
+            .into_iter()
+            .map(|(section, keys)| (section.to_string(), keys.into_iter().map(str::to_string).collect()))
+            .collect();
+        assert_eq!(names(&imported), expected);
+    }
+}
@@ -1,7 +1,22 @@
+use crate::bank_parser::attribute::Attribute;
 use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::VALUE_TAG;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Key {
     pub name: String,
     pub values: Vec<ValueElement>,
 }
+
+impl Key {
+    /// The typed value of the key: the first attribute of its `<Value>` element, or of
+    /// its first element if it has no `<Value>`.
+    pub fn value(&self) -> Option<&Attribute> {
+        self.values
+            .iter()
+            .find(|value| value.tag_name == VALUE_TAG)
+            .or_else(|| self.values.first())
+            .and_then(|value| value.attributes.first())
+    }
+}
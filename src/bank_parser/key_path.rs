@@ -0,0 +1,45 @@
+use crate::bank_parser::BankParserError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Address of a key within a bank, written `Section/Key`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyPath {
+    pub section: String,
+    pub key: String,
+}
+
+impl KeyPath {
+    pub fn new(section: impl Into<String>, key: impl Into<String>) -> Self {
+        KeyPath {
+            section: section.into(),
+            key: key.into(),
+        }
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.section, self.key)
+    }
+}
+
+impl FromStr for KeyPath {
+    type Err = BankParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((section, key)) if !section.is_empty() && !key.is_empty() => Ok(KeyPath::new(section, key)),
+            _ => Err(BankParserError::InvalidKeyPath(s.to_string())),
+        }
+    }
+}
+
+/// Key paths deserialize from their `Section/Key` text.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyPath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,407 @@
+use crate::bank_parser::diff::write_values;
+use crate::bank_parser::document::{Element, Formatting, Node};
+use crate::bank_parser::key::Key;
+use crate::bank_parser::key_path::KeyPath;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::{BankParser, KEY_TAG, NAME_ATTRIBUTE, SECTION_TAG, SIGNATURE_TAG};
+use crate::AppResult;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Three-way merge of banks over the `Section`/`Key` model.
+///
+/// Each key is merged on its own: a key that only one side changed (including adding
+/// or removing it) takes that side's values, and a key both sides changed the same way
+/// takes the shared values. A key both sides changed differently is a conflict; the
+/// merged sections keep our values for it.
+///
+/// A key listed more than once in a section is kept as often as it is listed: the
+/// first of them on one side is merged with the first on the others, and so on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BankMerge {
+    pub sections: Vec<Section>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A key changed differently on both sides. `None` means the key is absent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub key: KeyPath,
+    /// Which of the keys listed under `key` this is, from 0.
+    pub occurrence: usize,
+    pub base: Option<Vec<ValueElement>>,
+    pub ours: Option<Vec<ValueElement>>,
+    pub theirs: Option<Vec<ValueElement>>,
+}
+
+/// The values of a key in the base, our and their version, `None` where it is absent.
+type Versions = [Option<Vec<ValueElement>>; 3];
+
+impl BankMerge {
+    pub fn new(base: &[Section], ours: &[Section], theirs: &[Section]) -> Self {
+        // By section, key and occurrence of the key.
+        let mut keys: BTreeMap<(&str, &str, usize), Versions> = BTreeMap::new();
+        for (side, sections) in [base, ours, theirs].into_iter().enumerate() {
+            let mut listed: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+            for section in sections {
+                for key in &section.keys {
+                    let occurrence = listed.entry((section.name.as_str(), key.name.as_str())).or_default();
+                    let slot = (section.name.as_str(), key.name.as_str(), *occurrence);
+                    keys.entry(slot).or_default()[side] = Some(normalized(&key.values));
+                    *occurrence += 1;
+                }
+            }
+        }
+
+        let mut merged: BTreeMap<&str, Vec<Key>> = BTreeMap::new();
+        let mut conflicts = Vec::new();
+        for ((section, key, occurrence), [base, ours, theirs]) in keys {
+            let values = if ours == theirs || theirs == base {
+                ours
+            } else if ours == base {
+                theirs
+            } else {
+                conflicts.push(MergeConflict {
+                    key: KeyPath::new(section, key),
+                    occurrence,
+                    base,
+                    ours: ours.clone(),
+                    theirs,
+                });
+                ours
+            };
+            if let Some(values) = values {
+                merged.entry(section).or_default().push(Key { name: key.to_string(), values });
+            }
+        }
+
+        BankMerge {
+            sections: merged
+                .into_iter()
+                .map(|(name, keys)| Section { name: name.to_string(), keys })
+                .collect(),
+            conflicts,
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+impl BankParser {
+    /// Makes the bank hold the keys of `merge`, editing its document in place.
+    ///
+    /// Meant for our version of the bank, from which the merge keeps every key it does
+    /// not take from theirs: those keys are left as they are, along with the comments,
+    /// layout and sections and keys without values, so that only the keys the merge
+    /// changed, added or removed differ. Added keys go at the end of their section.
+    pub fn apply_merge(&mut self, merge: &BankMerge) -> AppResult<()> {
+        let current = by_occurrence(&self.sections);
+        let mut merged = by_occurrence(&merge.sections);
+        self.edit_document(|root, formatting| {
+            let mut listed: BTreeMap<(String, String), usize> = BTreeMap::new();
+            // Node indices of the keys to remove, as (section, key).
+            let mut removed = Vec::new();
+            for (section_index, node) in root.children.iter_mut().enumerate() {
+                let Node::Element(section_element) = node else { continue };
+                if section_element.name != SECTION_TAG {
+                    continue;
+                }
+                let Some(section) = section_element.attribute(NAME_ATTRIBUTE).map(str::to_string) else { continue };
+                for (key_index, node) in section_element.children.iter_mut().enumerate() {
+                    let Node::Element(key_element) = node else { continue };
+                    // Keys without values are not part of the merge.
+                    if key_element.name != KEY_TAG || key_element.elements().next().is_none() {
+                        continue;
+                    }
+                    let Some(key) = key_element.attribute(NAME_ATTRIBUTE).map(str::to_string) else { continue };
+                    let occurrence = listed.entry((section.clone(), key.clone())).or_default();
+                    let slot = (section.clone(), key, *occurrence);
+                    *occurrence += 1;
+                    match merged.remove(&slot) {
+                        None => removed.push((section_index, key_index)),
+                        Some(values) if current.get(&slot) != Some(&values) => {
+                            set_values(key_element, &values, formatting)
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+            for (section_index, key_index) in removed.into_iter().rev() {
+                if let Some(section_element) = root.child_element_mut(section_index) {
+                    section_element.remove_element(key_index);
+                }
+            }
+
+            for ((section, key, _), values) in merged {
+                let section_index = match root.find_named_child(SECTION_TAG, &section) {
+                    Some(index) => index,
+                    None => {
+                        let before = root.find_child(SIGNATURE_TAG);
+                        let element = Element::new(SECTION_TAG).with_attribute(NAME_ATTRIBUTE, &section);
+                        root.insert_element(before, element, formatting, 0)
+                    }
+                };
+                let mut key_element = Element::new(KEY_TAG).with_attribute(NAME_ATTRIBUTE, key);
+                set_values(&mut key_element, &values, formatting);
+                root.child_element_mut(section_index)
+                    .expect("section index points at an element")
+                    .insert_element(None, key_element, formatting, 1);
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The values of each key by section, key and occurrence of the key, normalized.
+fn by_occurrence(sections: &[Section]) -> BTreeMap<(String, String, usize), Vec<ValueElement>> {
+    let mut keys = BTreeMap::new();
+    let mut listed: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for section in sections {
+        for key in &section.keys {
+            let occurrence = listed.entry((section.name.as_str(), key.name.as_str())).or_default();
+            keys.insert((section.name.clone(), key.name.clone(), *occurrence), normalized(&key.values));
+            *occurrence += 1;
+        }
+    }
+    keys
+}
+
+/// Replaces the value elements of a key: in place if it holds elements with the same tags,
+/// so the key keeps its layout, or else with the values on their own lines.
+fn set_values(key_element: &mut Element, values: &[ValueElement], formatting: &Formatting) {
+    if key_element.elements().map(|element| &element.name).eq(values.iter().map(|value| &value.tag_name)) {
+        for (element, value) in key_element.elements_mut().zip(values) {
+            element.attributes = Element::from_value(value).attributes;
+        }
+        return;
+    }
+    while let Some(index) = key_element.children.iter().position(|node| matches!(node, Node::Element(_))) {
+        key_element.remove_element(index);
+    }
+    for value in values {
+        key_element.insert_element(None, Element::from_value(value), formatting, 2);
+    }
+}
+
+/// The values with attributes in name order, so attribute order does not count as a change.
+fn normalized(values: &[ValueElement]) -> Vec<ValueElement> {
+    let mut values = values.to_vec();
+    for value in &mut values {
+        value.attributes.sort_by(|a, b| a.name().cmp(b.name()));
+    }
+    values
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key)?;
+        if self.occurrence > 0 {
+            write!(f, " (listed #{})", self.occurrence + 1)?;
+        }
+        write!(f, ":")?;
+        for (side, values) in [("base", &self.base), ("ours", &self.ours), ("theirs", &self.theirs)] {
+            write!(f, " {} ", side)?;
+            match values {
+                Some(values) => write_values(f, values)?,
+                None => write!(f, "(absent)")?,
+            }
+            if side != "theirs" {
+                write!(f, ",")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank_parser::attribute::Attribute;
+    use crate::bank_parser::{BankParser, ParseMode};
+    use crate::signing_context::SigningContext;
+
+    /// The sections of a bank holding `keys`, each `(section, key, int value)`.
+    fn bank(keys: &[(&str, &str, i32)]) -> Vec<Section> {
+        let mut xml = String::from("<Bank version=\"1\">");
+        for (section, key, value) in keys {
+            let key = format!(r#"<Key name="{}"><Value int="{}"/></Key>"#, key, value);
+            xml += &format!(r#"<Section name="{}">{}</Section>"#, section, key);
+        }
+        xml += "</Bank>";
+        let context = SigningContext::unsigned("Bank");
+        BankParser::from_reader_with_mode(xml.as_bytes(), context, ParseMode::Strict).unwrap().sections
+    }
+
+    /// The merged keys as `(section, key, int value)`.
+    fn keys(merge: &BankMerge) -> Vec<(String, String, i32)> {
+        let mut keys = Vec::new();
+        for section in &merge.sections {
+            for key in &section.keys {
+                let Some(Attribute::Int(value)) = key.value() else { panic!("{:?}", key) };
+                keys.push((section.name.clone(), key.name.clone(), *value));
+            }
+        }
+        keys
+    }
+
+    fn key(section: &str, key: &str, value: i32) -> (String, String, i32) {
+        (section.to_string(), key.to_string(), value)
+    }
+
+    #[test]
+    fn takes_the_side_that_changed() {
+        let base = bank(&[("A", "K", 1), ("A", "L", 1), ("A", "M", 1)]);
+        let ours = bank(&[("A", "K", 2), ("A", "L", 1), ("A", "M", 1)]);
+        let theirs = bank(&[("A", "K", 1), ("A", "L", 3)]);
+        let merge = BankMerge::new(&base, &ours, &theirs);
+        assert!(merge.is_clean());
+        assert_eq!(keys(&merge), [key("A", "K", 2), key("A", "L", 3)]);
+    }
+
+    #[test]
+    fn both_sides_changing_a_key_conflicts_unless_they_agree() {
+        let base = bank(&[("A", "K", 1), ("A", "L", 1)]);
+        let ours = bank(&[("A", "K", 2), ("A", "L", 2)]);
+        let theirs = bank(&[("A", "K", 3), ("A", "L", 2)]);
+        let merge = BankMerge::new(&base, &ours, &theirs);
+        assert_eq!(keys(&merge), [key("A", "K", 2), key("A", "L", 2)]);
+        assert_eq!(merge.conflicts.len(), 1);
+        let conflict = &merge.conflicts[0];
+        assert_eq!(conflict.key, KeyPath::new("A", "K"));
+        assert_eq!(conflict.to_string(), "A/K: base int=1, ours int=2, theirs int=3");
+    }
+
+    #[test]
+    fn deleting_a_key_the_other_side_changed_conflicts() {
+        let base = bank(&[("A", "K", 1), ("A", "L", 1)]);
+        let ours = bank(&[("A", "L", 2)]);
+        let theirs = bank(&[("A", "K", 2)]);
+        let merge = BankMerge::new(&base, &ours, &theirs);
+        assert_eq!(keys(&merge), [key("A", "L", 2)]);
+        let conflicts: Vec<String> = merge.conflicts.iter().map(MergeConflict::to_string).collect();
+        assert_eq!(
+            conflicts,
+            [
+                "A/K: base int=1, ours (absent), theirs int=2",
+                "A/L: base int=1, ours int=2, theirs (absent)",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_additions_of_both_sides() {
+        let base = bank(&[("A", "K", 1)]);
+        let ours = bank(&[("A", "K", 1), ("A", "Ours", 1), ("B", "Same", 5), ("C", "Both", 1)]);
+        let theirs = bank(&[("A", "K", 1), ("A", "Theirs", 2), ("B", "Same", 5), ("C", "Both", 2)]);
+        let merge = BankMerge::new(&base, &ours, &theirs);
+        assert_eq!(
+            keys(&merge),
+            [
+                key("A", "K", 1),
+                key("A", "Ours", 1),
+                key("A", "Theirs", 2),
+                key("B", "Same", 5),
+                key("C", "Both", 1),
+            ]
+        );
+        assert_eq!(merge.conflicts.len(), 1);
+        assert_eq!(merge.conflicts[0].to_string(), "C/Both: base (absent), ours int=1, theirs int=2");
+    }
+
+    #[test]
+    fn keeps_keys_listed_more_than_once() {
+        let base = bank(&[("A", "K", 1), ("A", "K", 2)]);
+        let ours = bank(&[("A", "K", 1), ("A", "K", 3)]);
+        let theirs = bank(&[("A", "K", 1), ("A", "K", 2), ("A", "K", 4)]);
+        let merge = BankMerge::new(&base, &ours, &theirs);
+        assert!(merge.is_clean());
+        assert_eq!(keys(&merge), [key("A", "K", 1), key("A", "K", 3), key("A", "K", 4)]);
+
+        let theirs = bank(&[("A", "K", 1), ("A", "K", 5)]);
+        let merge = BankMerge::new(&base, &ours, &theirs);
+        assert_eq!(keys(&merge), [key("A", "K", 1), key("A", "K", 3)]);
+        assert_eq!(merge.conflicts.len(), 1);
+        assert_eq!(merge.conflicts[0].occurrence, 1);
+        assert_eq!(merge.conflicts[0].to_string(), "A/K (listed #2): base int=2, ours int=3, theirs int=5");
+    }
+
+    #[test]
+    fn applies_the_merge_to_our_document() {
+        let ours = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- saved by the map -->
+<Bank version="1">
+    <Section name="A">
+        <Key name="K"><Value int="1"/></Key>
+        <Key name="L"><Value int="1"/></Key>
+        <Key name="Empty"/>
+        <Key name="M">
+            <Value int="1"/>
+        </Key>
+    </Section>
+    <Section name="Unused"/>
+    <Signature value="ABC"/>
+</Bank>
+"#;
+        let context = SigningContext::new("Bank", "1-S2-1-1234567".parse().unwrap(), "2-S2-1-7654321".parse().unwrap());
+        let mut merged = BankParser::from_str(ours, context.clone()).unwrap();
+        let base = bank(&[("A", "K", 1), ("A", "L", 1), ("A", "M", 1)]);
+        let theirs = bank(&[("A", "K", 2), ("A", "M", 1), ("A", "N", 3), ("B", "O", 4)]);
+        let merge = BankMerge::new(&base, &merged.sections, &theirs);
+        assert!(merge.is_clean());
+        merged.apply_merge(&merge).unwrap();
+        merged.apply_signature().unwrap();
+
+        let expected = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- saved by the map -->
+<Bank version="1">
+    <Section name="A">
+        <Key name="K"><Value int="2"/></Key>
+        <Key name="Empty"/>
+        <Key name="M">
+            <Value int="1"/>
+        </Key>
+        <Key name="N">
+            <Value int="3"/>
+        </Key>
+    </Section>
+    <Section name="Unused"/>
+    <Section name="B">
+        <Key name="O">
+            <Value int="4"/>
+        </Key>
+    </Section>
+    <Signature value="SIGNATURE"/>
+</Bank>
+"#;
+        assert_eq!(merged.to_xml(), expected.replace("SIGNATURE", &merged.signature));
+        let reread = BankParser::from_str(&merged.to_xml(), context).unwrap();
+        assert_eq!(reread.sections, merge.sections);
+        assert_eq!(reread.signature, merged.signature);
+    }
+
+    #[test]
+    fn applies_the_merge_to_keys_listed_more_than_once() {
+        let base = bank(&[("A", "K", 1), ("A", "K", 2), ("A", "K", 3)]);
+        let ours = concat!(
+            r#"<Bank version="1"><Section name="A">"#,
+            r#"<Key name="K"><Value int="1"/></Key><Key name="K"><Value int="2"/></Key>"#,
+            r#"<Key name="K"><Value int="3"/></Key>"#,
+            r#"</Section></Bank>"#,
+        );
+        let theirs = bank(&[("A", "K", 1), ("A", "K", 5)]);
+        let mut merged = BankParser::from_str(ours, SigningContext::unsigned("Bank")).unwrap();
+        let merge = BankMerge::new(&base, &merged.sections, &theirs);
+        merged.apply_merge(&merge).unwrap();
+        assert_eq!(
+            merged.to_xml(),
+            concat!(
+                r#"<Bank version="1"><Section name="A">"#,
+                r#"<Key name="K"><Value int="1"/></Key><Key name="K"><Value int="5"/></Key>"#,
+                r#"</Section></Bank>"#,
+            )
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::fixed::Fixed;
+use crate::bank_parser::key_path::KeyPath;
+use crate::bank_parser::{BankParser, BankParserError};
+use crate::AppResult;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A list of edits to apply to banks, read from JSON or TOML.
+///
+/// Keys are addressed as `Section/Key` and values written as `TYPE=VALUE`, as on the
+/// command line. Operations that address a key take an optional `expect`: the value
+/// the key must currently have for the patch to apply, compared by type and value, so
+/// `fixed=1.50` holds for a key written `fixed="1.5"`.
+///
+/// ```toml
+/// [[operations]]
+/// op = "set"
+/// key = "Stats/Gold"
+/// value = "int=10000"
+/// expect = "int=100"
+///
+/// [[operations]]
+/// op = "increment"
+/// key = "Stats/Kills"
+/// by = 5
+///
+/// [[operations]]
+/// op = "ensure-section"
+/// section = "Heroes"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Patch {
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum PatchOperation {
+    /// Sets the key's value, creating the section and key if needed.
+    Set {
+        key: KeyPath,
+        #[serde(deserialize_with = "assignment")]
+        value: Attribute,
+        #[serde(default, deserialize_with = "optional_assignment")]
+        expect: Option<Attribute>,
+    },
+    Remove {
+        key: KeyPath,
+        #[serde(default, deserialize_with = "optional_assignment")]
+        expect: Option<Attribute>,
+    },
+    /// Renames the key within its section.
+    Rename {
+        key: KeyPath,
+        to: String,
+        #[serde(default, deserialize_with = "optional_assignment")]
+        expect: Option<Attribute>,
+    },
+    RenameSection { section: String, to: String },
+    /// Adds `by` to an `int` or `fixed` value.
+    Increment {
+        key: KeyPath,
+        by: Amount,
+        #[serde(default, deserialize_with = "optional_assignment")]
+        expect: Option<Attribute>,
+    },
+    /// Adds the section, empty, unless it exists.
+    EnsureSection { section: String },
+}
+
+/// A number to increment by, kept as text until the type of the value is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Amount(pub String);
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Number {
+            Int(i64),
+            Float(f64),
+            Text(String),
+        }
+        Ok(Amount(match Number::deserialize(deserializer)? {
+            Number::Int(v) => v.to_string(),
+            Number::Float(v) => v.to_string(),
+            Number::Text(v) => v,
+        }))
+    }
+}
+
+fn assignment<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Attribute, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Attribute::from_str(&text).map_err(serde::de::Error::custom)
+}
+
+fn optional_assignment<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Attribute>, D::Error> {
+    assignment(deserializer).map(Some)
+}
+
+impl Patch {
+    /// Reads a patch file: TOML if its extension is `.toml`, JSON otherwise.
+    pub fn from_path(path: impl AsRef<Path>) -> AppResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml")) {
+            Self::from_toml(&text)
+        } else {
+            Self::from_json(&text)
+        }
+    }
+
+    pub fn from_json(text: &str) -> AppResult<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn from_toml(text: &str) -> AppResult<Self> {
+        Ok(toml::from_str(text)?)
+    }
+}
+
+impl BankParser {
+    /// Applies the operations of `patch` in order and re-signs the bank.
+    ///
+    /// Stops at the first operation that fails, including an `expect` that does not
+    /// hold, leaving the bank partly patched; callers wanting all or nothing should
+    /// patch a copy or discard the bank on error.
+    pub fn apply_patch(&mut self, patch: &Patch) -> AppResult<()> {
+        for operation in &patch.operations {
+            self.apply_operation(operation)?;
+        }
+        self.apply_signature()
+    }
+
+    fn apply_operation(&mut self, operation: &PatchOperation) -> AppResult<()> {
+        match operation {
+            PatchOperation::Set { key, value, expect } => {
+                self.check_expectation(key, expect.as_ref())?;
+                self.set_value(&key.section, &key.key, value.clone())
+            }
+            PatchOperation::Remove { key, expect } => {
+                self.check_expectation(key, expect.as_ref())?;
+                self.remove_key(&key.section, &key.key)
+            }
+            PatchOperation::Rename { key, to, expect } => {
+                self.check_expectation(key, expect.as_ref())?;
+                self.rename_key(&key.section, &key.key, to)
+            }
+            PatchOperation::RenameSection { section, to } => self.rename_section(section, to),
+            PatchOperation::Increment { key, by, expect } => {
+                self.check_expectation(key, expect.as_ref())?;
+                let value = self.value(&key.section, &key.key).cloned().ok_or_else(|| {
+                    BankParserError::KeyNotFound { section: key.section.clone(), key: key.key.clone() }
+                })?;
+                let incremented = match &value {
+                    Attribute::Int(v) => by.0.parse().ok().and_then(|by| v.checked_add(by)).map(Attribute::Int),
+                    Attribute::Fixed(v, _) => by
+                        .0
+                        .parse::<Fixed>()
+                        .ok()
+                        .and_then(|by| v.checked_add(by))
+                        .map(|sum| Attribute::Fixed(sum, None)),
+                    _ => None,
+                };
+                let incremented = incremented.ok_or_else(|| BankParserError::InvalidIncrement {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    by: by.to_string(),
+                })?;
+                self.set_value(&key.section, &key.key, incremented)
+            }
+            PatchOperation::EnsureSection { section } => self.ensure_section(section),
+        }
+    }
+
+    fn check_expectation(&self, key: &KeyPath, expect: Option<&Attribute>) -> Result<(), BankParserError> {
+        let Some(expected) = expect else {
+            return Ok(());
+        };
+        let found = self.value(&key.section, &key.key);
+        if found.is_some_and(|found| found.same_value(expected)) {
+            return Ok(());
+        }
+        Err(BankParserError::UnexpectedValue {
+            key: key.to_string(),
+            expected: expected.to_string(),
+            found: found.map_or_else(|| "no value".to_string(), Attribute::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_context::SigningContext;
+
+    const SOURCE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Bank version="1">
+    <Section name="Stats">
+        <Key name="Gold"><Value int="100"/></Key>
+        <Key name="Rate"><Value fixed="1.5"/></Key>
+        <Key name="Name"><Value string="Raynor"/></Key>
+        <Key name="Music"><Value flag="1"/></Key>
+    </Section>
+    <Signature value="ABC"/>
+</Bank>
+"#;
+
+    fn bank() -> BankParser {
+        let context = SigningContext::new("Bank", "1-S2-1-1234567".parse().unwrap(), "2-S2-1-7654321".parse().unwrap());
+        BankParser::from_str(SOURCE, context).unwrap()
+    }
+
+    fn patch(json: &str) -> Patch {
+        Patch::from_json(&format!(r#"{{"operations": [{}]}}"#, json)).unwrap()
+    }
+
+    fn error(result: AppResult<()>) -> String {
+        match result.unwrap_err() {
+            crate::AppError::BankParseError(err) => err.to_string(),
+            err => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn reads_toml_and_json() {
+        let toml = Patch::from_toml(
+            r#"
+[[operations]]
+op = "set"
+key = "Stats/Gold"
+value = "int=10000"
+expect = "int=100"
+
+[[operations]]
+op = "increment"
+key = "Stats/Rate"
+by = 0.25
+
+[[operations]]
+op = "ensure-section"
+section = "Heroes"
+"#,
+        )
+        .unwrap();
+        let json = Patch::from_json(
+            r#"{"operations": [
+                {"op": "set", "key": "Stats/Gold", "value": "int=10000", "expect": "int=100"},
+                {"op": "increment", "key": "Stats/Rate", "by": 0.25},
+                {"op": "ensure-section", "section": "Heroes"}
+            ]}"#,
+        )
+        .unwrap();
+        for patch in [toml, json] {
+            assert_eq!(patch.operations.len(), 3);
+            let PatchOperation::Set { key, value, expect } = &patch.operations[0] else {
+                panic!("{:?}", patch.operations[0]);
+            };
+            assert_eq!(key, &KeyPath::new("Stats", "Gold"));
+            assert_eq!(value, &Attribute::Int(10000));
+            assert_eq!(expect, &Some(Attribute::Int(100)));
+            let PatchOperation::Increment { by, expect: None, .. } = &patch.operations[1] else {
+                panic!("{:?}", patch.operations[1]);
+            };
+            assert_eq!(by, &Amount("0.25".to_string()));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_operations_fields_and_values() {
+        assert!(Patch::from_json(r#"{"operations": [{"op": "delete", "key": "Stats/Gold"}]}"#).is_err());
+        assert!(Patch::from_json(r#"{"operations": [{"op": "remove", "key": "Stats/Gold", "to": "X"}]}"#).is_err());
+        assert!(Patch::from_json(r#"{"operations": [{"op": "remove", "key": "Gold"}]}"#).is_err());
+        assert!(Patch::from_json(r#"{"operations": [{"op": "set", "key": "Stats/Gold", "value": "int=x"}]}"#).is_err());
+        assert!(Patch::from_toml("[[operations]]\nop = \"set\"\nkey = \"Stats/Gold\"\n").is_err());
+    }
+
+    #[test]
+    fn applies_every_operation() {
+        let mut bank = bank();
+        let signature = bank.signature.clone();
+        bank.apply_patch(&patch(
+            r#"{"op": "set", "key": "Stats/Name", "value": "string=Kerrigan"},
+               {"op": "set", "key": "New/Key", "value": "int=1"},
+               {"op": "remove", "key": "Stats/Music"},
+               {"op": "rename", "key": "Stats/Gold", "to": "Coins"},
+               {"op": "rename-section", "section": "Stats", "to": "Purse"},
+               {"op": "increment", "key": "Purse/Coins", "by": 5},
+               {"op": "increment", "key": "Purse/Rate", "by": "0.25"},
+               {"op": "ensure-section", "section": "Heroes"}"#,
+        ))
+        .unwrap();
+        assert_eq!(bank.get_string("Purse", "Name"), Some("Kerrigan"));
+        assert_eq!(bank.get_int("New", "Key"), Some(1));
+        assert!(bank.key("Purse", "Music").is_none());
+        assert_eq!(bank.get_int("Purse", "Coins"), Some(105));
+        assert_eq!(bank.get_fixed("Purse", "Rate"), Some(Fixed::new(1.75)));
+        assert!(bank.section("Stats").is_none());
+        assert!(bank.to_xml().contains(r#"<Section name="Heroes"/>"#));
+        assert_ne!(bank.signature, signature);
+        let reread = BankParser::from_str(&bank.to_xml(), bank.context.clone()).unwrap();
+        assert_eq!(reread.signature, bank.signature);
+    }
+
+    #[test]
+    fn checks_expected_values_by_type() {
+        let mut bank = bank();
+        bank.apply_patch(&patch(
+            r#"{"op": "set", "key": "Stats/Gold", "value": "int=1", "expect": "int=100"},
+               {"op": "set", "key": "Stats/Rate", "value": "fixed=2", "expect": "fixed=1.50"},
+               {"op": "set", "key": "Stats/Music", "value": "flag=0", "expect": "flag=1"},
+               {"op": "remove", "key": "Stats/Name", "expect": "string=Raynor"}"#,
+        ))
+        .unwrap();
+        assert_eq!(bank.get_int("Stats", "Gold"), Some(1));
+        assert_eq!(bank.get_fixed("Stats", "Rate"), Some(Fixed::new(2.0)));
+        assert_eq!(bank.get_flag("Stats", "Music"), Some(false));
+        assert!(bank.key("Stats", "Name").is_none());
+    }
+
+    #[test]
+    fn rejects_values_not_as_expected() {
+        let mut bank = bank();
+        assert_eq!(
+            error(bank.apply_patch(&patch(r#"{"op": "remove", "key": "Stats/Gold", "expect": "int=99"}"#))),
+            BankParserError::UnexpectedValue {
+                key: "Stats/Gold".to_string(),
+                expected: "int=99".to_string(),
+                found: "int=100".to_string(),
+            }
+            .to_string()
+        );
+        assert!(bank.apply_patch(&patch(r#"{"op": "remove", "key": "Stats/Gold", "expect": "fixed=100"}"#)).is_err());
+        let rename = patch(r#"{"op": "rename", "key": "Stats/Rate", "to": "R", "expect": "fixed=1.25"}"#);
+        assert!(bank.apply_patch(&rename).is_err());
+        let set = patch(r#"{"op": "set", "key": "Stats/Lives", "value": "int=1", "expect": "int=0"}"#);
+        assert!(error(bank.apply_patch(&set)).contains("no value"));
+        assert_eq!(bank.to_xml(), SOURCE);
+    }
+
+    #[test]
+    fn increments_only_numbers() {
+        let mut bank = bank();
+        let invalid = |by: &str, value: &str, key: &str| {
+            BankParserError::InvalidIncrement { key: key.to_string(), value: value.to_string(), by: by.to_string() }
+                .to_string()
+        };
+        let increment =
+            |key: &str, by: &str| patch(&format!(r#"{{"op": "increment", "key": "{}", "by": {}}}"#, key, by));
+        assert_eq!(error(bank.apply_patch(&increment("Stats/Name", "1"))), invalid("1", "string=Raynor", "Stats/Name"));
+        assert_eq!(error(bank.apply_patch(&increment("Stats/Gold", "0.5"))), invalid("0.5", "int=100", "Stats/Gold"));
+        assert_eq!(
+            error(bank.apply_patch(&increment("Stats/Gold", "2147483647"))),
+            invalid("2147483647", "int=100", "Stats/Gold")
+        );
+        assert!(bank.apply_patch(&increment("Stats/Missing", "1")).is_err());
+        bank.apply_patch(&increment("Stats/Gold", "-100")).unwrap();
+        assert_eq!(bank.get_int("Stats", "Gold"), Some(0));
+    }
+}
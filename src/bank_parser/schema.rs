@@ -0,0 +1,477 @@
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::diagnostic::{Diagnostic, Span};
+use crate::bank_parser::document::Element;
+use crate::bank_parser::fixed::Fixed;
+use crate::bank_parser::key::Key;
+use crate::bank_parser::key_path::KeyPath;
+use crate::bank_parser::{BankParser, KEY_TAG, SECTION_TAG, VALUE_TAG};
+use crate::AppResult;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// What a bank is expected to hold: its sections, their keys and the values those may
+/// have. Read from a `.bankschema` file, which is TOML, or from JSON.
+///
+/// Every key has a `type`, one of `int`, `fixed`, `flag`, `string` and `text`, and is
+/// optional unless `required` is set. `min` and `max` bound `int` and `fixed` values,
+/// `enum` lists the values allowed, and `regex` must match the value as written in
+/// the bank.
+///
+/// ```toml
+/// [sections.Stats.keys.Gold]
+/// type = "int"
+/// required = true
+/// min = 0
+/// max = 1000000
+///
+/// [sections.Options.keys.Difficulty]
+/// type = "string"
+/// enum = ["easy", "normal", "hard"]
+///
+/// [sections.Profile.keys.Title]
+/// type = "text"
+/// regex = "^[A-Za-z ]{1,24}$"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    #[serde(default)]
+    pub sections: BTreeMap<String, SectionSchema>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionSchema {
+    #[serde(default)]
+    pub keys: BTreeMap<String, KeySchema>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeySchema {
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, deserialize_with = "bound")]
+    pub min: Option<f64>,
+    #[serde(default, deserialize_with = "bound")]
+    pub max: Option<f64>,
+    /// The values allowed, if only some are.
+    #[serde(default, rename = "enum")]
+    pub allowed: Option<Vec<SchemaValue>>,
+    #[serde(default, deserialize_with = "regex")]
+    pub regex: Option<Regex>,
+}
+
+/// The attribute type a key's value is written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    Int,
+    Fixed,
+    Flag,
+    String,
+    Text,
+}
+
+impl ValueType {
+    /// Whether `attribute` is of this type.
+    pub fn holds(self, attribute: &Attribute) -> bool {
+        matches!(
+            (self, attribute),
+            (ValueType::Int, Attribute::Int(_))
+                | (ValueType::Fixed, Attribute::Fixed(..))
+                | (ValueType::Flag, Attribute::Flag(_))
+                | (ValueType::String, Attribute::String(_))
+                | (ValueType::Text, Attribute::Text(_))
+        )
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Int => "int",
+            ValueType::Fixed => "fixed",
+            ValueType::Flag => "flag",
+            ValueType::String => "string",
+            ValueType::Text => "text",
+        };
+        f.pad(name)
+    }
+}
+
+/// An entry of an `enum`: a number for `int` and `fixed` keys, a boolean for `flag`
+/// keys, and text for `string` and `text` keys.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum SchemaValue {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl SchemaValue {
+    pub fn matches(&self, attribute: &Attribute) -> bool {
+        match (self, attribute) {
+            (SchemaValue::Number(number), Attribute::Int(value)) => f64::from(*value) == *number,
+            (SchemaValue::Number(number), Attribute::Fixed(value, _)) => Fixed::new(*number) == *value,
+            (SchemaValue::Bool(flag), Attribute::Flag(value)) => value.0 == *flag,
+            (SchemaValue::Text(text), Attribute::String(value) | Attribute::Text(value)) => text == value,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for SchemaValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaValue::Bool(flag) => write!(f, "{}", flag),
+            SchemaValue::Number(number) => write!(f, "{}", number),
+            SchemaValue::Text(text) => write!(f, "'{}'", text),
+        }
+    }
+}
+
+/// Bounds may be written as integers or floats.
+fn bound<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Int(i64),
+        Float(f64),
+    }
+    Ok(Some(match Number::deserialize(deserializer)? {
+        Number::Int(v) => v as f64,
+        Number::Float(v) => v,
+    }))
+}
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map(Some).map_err(serde::de::Error::custom)
+}
+
+impl Schema {
+    /// Reads a schema file: JSON if its extension is `.json`, TOML otherwise.
+    pub fn from_path(path: impl AsRef<Path>) -> AppResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    pub fn from_json(text: &str) -> AppResult<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn from_toml(text: &str) -> AppResult<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Checks `bank` against the schema, returning a diagnostic for each unknown
+    /// section or key, missing required key, value of the wrong type, and value out
+    /// of its range or not allowed by its `enum` or `regex`. Diagnostics are located
+    /// at the offending element or value where the bank was read from a source.
+    pub fn validate(&self, bank: &BankParser) -> Vec<Diagnostic> {
+        let root = &bank.document.root;
+        let mut diagnostics = Vec::new();
+        for section in &bank.sections {
+            let section_element = root
+                .find_named_child(SECTION_TAG, &section.name)
+                .and_then(|index| root.child_element(index));
+            let Some(section_schema) = self.sections.get(&section.name) else {
+                diagnostics.push(Diagnostic::error(
+                    format!("{}: unknown section", section.name),
+                    section_element.and_then(|element| element.span),
+                ));
+                continue;
+            };
+            for key in &section.keys {
+                let path = KeyPath::new(&section.name, &key.name);
+                let key_element = section_element.and_then(|element| {
+                    element.find_named_child(KEY_TAG, &key.name).and_then(|index| element.child_element(index))
+                });
+                let Some(key_schema) = section_schema.keys.get(&key.name) else {
+                    diagnostics.push(Diagnostic::error(
+                        format!("{}: unknown key", path),
+                        key_element.and_then(|element| element.span),
+                    ));
+                    continue;
+                };
+                for message in key_schema.check(key) {
+                    let span = value_span(key, key_element).or_else(|| key_element.and_then(|element| element.span));
+                    diagnostics.push(Diagnostic::error(format!("{}: {}", path, message), span));
+                }
+            }
+        }
+
+        for (section_name, section_schema) in &self.sections {
+            for (key_name, key_schema) in &section_schema.keys {
+                if key_schema.required && bank.key(section_name, key_name).is_none() {
+                    diagnostics.push(Diagnostic::error(
+                        format!("{}: required key is missing", KeyPath::new(section_name, key_name)),
+                        None,
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+impl KeySchema {
+    /// What is wrong with the value of `key`, one message per problem.
+    pub fn check(&self, key: &Key) -> Vec<String> {
+        let Some(value) = key.value() else {
+            return vec![format!("expected {} value, found none", self.value_type)];
+        };
+        if !self.value_type.holds(value) {
+            return vec![format!("expected {} value, found {}", self.value_type, value)];
+        }
+
+        let mut problems = Vec::new();
+        let number = match value {
+            Attribute::Int(v) => Some(f64::from(*v)),
+            Attribute::Fixed(v, _) => Some(v.to_f64()),
+            _ => None,
+        };
+        if let (Some(number), Some(min)) = (number, self.min)
+            && number < min
+        {
+            problems.push(format!("value {} is below the minimum {}", value, min));
+        }
+        if let (Some(number), Some(max)) = (number, self.max)
+            && number > max
+        {
+            problems.push(format!("value {} is above the maximum {}", value, max));
+        }
+        if let Some(allowed) = &self.allowed
+            && !allowed.iter().any(|entry| entry.matches(value))
+        {
+            let allowed: Vec<String> = allowed.iter().map(SchemaValue::to_string).collect();
+            problems.push(format!("value {} is not one of {}", value, allowed.join(", ")));
+        }
+        if let Some(regex) = &self.regex {
+            let mut text = String::new();
+            let _ = value.write_value(&mut text);
+            if !regex.is_match(&text) {
+                problems.push(format!("value {} does not match /{}/", value, regex));
+            }
+        }
+        problems
+    }
+}
+
+/// Where the value of `key` was found: its attribute in the element `Key::value` reads.
+fn value_span(key: &Key, key_element: Option<&Element>) -> Option<Span> {
+    let attribute = key.value()?;
+    let key_element = key_element?;
+    let value_element = key_element
+        .elements()
+        .find(|element| element.name == VALUE_TAG)
+        .or_else(|| key_element.elements().next())?;
+    value_element.attribute_span(attribute.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_context::SigningContext;
+
+    const SCHEMA: &str = r#"
+[sections.Stats.keys.Gold]
+type = "int"
+required = true
+min = 0
+max = 1000
+
+[sections.Stats.keys.Rate]
+type = "fixed"
+min = 0.5
+max = 2
+
+[sections.Stats.keys.Lives]
+type = "int"
+required = true
+
+[sections.Options.keys.Difficulty]
+type = "string"
+enum = ["easy", "normal", "hard"]
+
+[sections.Options.keys.Level]
+type = "int"
+enum = [1, 2, 3]
+
+[sections.Options.keys.Speed]
+type = "fixed"
+enum = [0.5, 1.5]
+
+[sections.Options.keys.Music]
+type = "flag"
+enum = [true]
+
+[sections.Options.keys.Title]
+type = "text"
+regex = "^[A-Za-z ]{1,24}$"
+"#;
+
+    fn schema() -> Schema {
+        Schema::from_toml(SCHEMA).unwrap()
+    }
+
+    /// The messages of the diagnostics of `source` against the schema, each with the
+    /// source text at its span.
+    fn validate(source: &str) -> Vec<(String, Option<&str>)> {
+        let bank = BankParser::from_str(source, SigningContext::unsigned("Bank")).unwrap();
+        schema()
+            .validate(&bank)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.message, diagnostic.span.map(|span| &source[span.start..span.end])))
+            .collect()
+    }
+
+    fn bank(stats: &str, options: &str) -> String {
+        format!(
+            r#"<Bank version="1"><Section name="Stats">{}</Section><Section name="Options">{}</Section></Bank>"#,
+            stats, options
+        )
+    }
+
+    const VALID_STATS: &str = r#"<Key name="Gold"><Value int="10"/></Key><Key name="Lives"><Value int="3"/></Key>"#;
+
+    fn key(name: &str, value: &str) -> String {
+        format!(r#"<Key name="{}"><Value {}/></Key>"#, name, value)
+    }
+
+    fn problem(message: &str, span: &'static str) -> (String, Option<&'static str>) {
+        (message.to_string(), Some(span))
+    }
+
+    #[test]
+    fn reads_toml_and_json() {
+        let json = r#"{"sections": {"Stats": {"keys": {"Gold": {"type": "int", "min": 0}}}}}"#;
+        let json = Schema::from_json(json).unwrap();
+        assert_eq!(json.sections["Stats"].keys["Gold"].value_type, ValueType::Int);
+        assert_eq!(json.sections["Stats"].keys["Gold"].min, Some(0.0));
+        let schema = schema();
+        assert_eq!(schema.sections["Stats"].keys["Rate"].max, Some(2.0));
+        assert!(Schema::from_toml("[sections.Stats.keys.Gold]\ntype = \"number\"").is_err());
+        assert!(Schema::from_toml("[sections.Stats.keys.Gold]\ntype = \"int\"\nmaximum = 1").is_err());
+        assert!(Schema::from_toml("[sections.Stats.keys.Gold]\ntype = \"text\"\nregex = \"(\"").is_err());
+    }
+
+    #[test]
+    fn accepts_valid_banks() {
+        let options = [
+            key("Difficulty", r#"string="hard""#),
+            key("Level", r#"int="2""#),
+            key("Speed", r#"fixed="1.50""#),
+            key("Music", r#"flag="1""#),
+            key("Title", r#"text="Jim Raynor""#),
+        ]
+        .concat();
+        let stats = format!("{}{}", VALID_STATS, key("Rate", r#"fixed="2""#));
+        assert_eq!(validate(&bank(&stats, &options)), []);
+    }
+
+    #[test]
+    fn reports_unknown_sections_and_keys() {
+        let source = format!(
+            r#"<Bank version="1"><Section name="Stats">{}{}</Section><Section name="Extra">{}</Section></Bank>"#,
+            VALID_STATS,
+            key("Unknown", r#"int="1""#),
+            key("K", r#"int="1""#)
+        );
+        assert_eq!(
+            validate(&source),
+            [
+                problem("Extra: unknown section", r#"<Section name="Extra">"#),
+                problem("Stats/Unknown: unknown key", r#"<Key name="Unknown">"#),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_required_keys() {
+        let source = bank(&key("Gold", r#"int="1""#), "");
+        assert_eq!(validate(&source), [("Stats/Lives: required key is missing".to_string(), None)]);
+    }
+
+    #[test]
+    fn reports_values_of_the_wrong_type() {
+        let stats = format!("{}{}", key("Gold", r#"fixed="1.5""#), key("Lives", r#"int="3""#));
+        assert_eq!(
+            validate(&bank(&stats, &key("Title", r#"string="Raynor""#))),
+            [
+                problem("Options/Title: expected text value, found string=Raynor", r#"string="Raynor""#),
+                problem("Stats/Gold: expected int value, found fixed=1.5", r#"fixed="1.5""#),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_values_out_of_range() {
+        let stats = [key("Gold", r#"int="-1""#), key("Lives", r#"int="3""#), key("Rate", r#"fixed="0.25""#)].concat();
+        assert_eq!(
+            validate(&bank(&stats, "")),
+            [
+                problem("Stats/Gold: value int=-1 is below the minimum 0", r#"int="-1""#),
+                problem("Stats/Rate: value fixed=0.25 is below the minimum 0.5", r#"fixed="0.25""#),
+            ]
+        );
+        let stats = [key("Gold", r#"int="1001""#), key("Lives", r#"int="3""#), key("Rate", r#"fixed="2.5""#)].concat();
+        assert_eq!(
+            validate(&bank(&stats, "")),
+            [
+                problem("Stats/Gold: value int=1001 is above the maximum 1000", r#"int="1001""#),
+                problem("Stats/Rate: value fixed=2.5 is above the maximum 2", r#"fixed="2.5""#),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_values_not_in_their_enum() {
+        let options = [
+            key("Difficulty", r#"string="brutal""#),
+            key("Level", r#"int="4""#),
+            key("Speed", r#"fixed="1""#),
+            key("Music", r#"flag="0""#),
+        ]
+        .concat();
+        assert_eq!(
+            validate(&bank(VALID_STATS, &options)),
+            [
+                problem(
+                    "Options/Difficulty: value string=brutal is not one of 'easy', 'normal', 'hard'",
+                    r#"string="brutal""#
+                ),
+                problem("Options/Level: value int=4 is not one of 1, 2, 3", r#"int="4""#),
+                problem("Options/Music: value flag=0 is not one of true", r#"flag="0""#),
+                problem("Options/Speed: value fixed=1 is not one of 0.5, 1.5", r#"fixed="1""#),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_values_not_matching_their_regex() {
+        assert_eq!(
+            validate(&bank(VALID_STATS, &key("Title", r#"text="Raynor 2""#))),
+            [problem("Options/Title: value text=Raynor 2 does not match /^[A-Za-z ]{1,24}$/", r#"text="Raynor 2""#)]
+        );
+    }
+
+    #[test]
+    fn checks_keys_without_a_value() {
+        let schema = schema();
+        let key = Key { name: "Gold".to_string(), values: Vec::new() };
+        assert_eq!(schema.sections["Stats"].keys["Gold"].check(&key), ["expected int value, found none"]);
+    }
+}
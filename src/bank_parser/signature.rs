@@ -0,0 +1,540 @@
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::diagnostic::Diagnostic;
+use crate::bank_parser::document::qualified_name;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::value_element::ValueElement;
+use crate::bank_parser::{BankParser, ParseMode, KEY_TAG, NAME_ATTRIBUTE, SECTION_TAG, SIGNATURE_TAG};
+use crate::signing_context::SigningContext;
+use crate::AppResult;
+use sha1::{Digest, Sha1};
+use std::fmt;
+use std::path::PathBuf;
+use xml::attribute::OwnedAttribute;
+use xml::reader::XmlEvent;
+use xml::EventReader;
+
+/// Feeds the signing payload into SHA-1 one item at a time.
+///
+/// The payload is the concatenation of the context handles, the bank name, and then,
+/// for each section and key sorted by name, their names, the tag names of their value
+/// elements, and each value's attribute names and values sorted by attribute name.
+/// `text` values are the exception: only their attribute name is signed.
+pub struct PayloadHasher {
+    hasher: Sha1,
+    /// Reused to format typed values without a new allocation per value.
+    scratch: String,
+    /// Every item seen, when recording the payload for inspection.
+    items: Option<Vec<PayloadItem>>,
+}
+
+/// What a payload item stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadItemKind {
+    AuthorHandle,
+    PlayerHandle,
+    BankName,
+    Section,
+    Key,
+    Tag,
+    AttributeName,
+    AttributeValue,
+    /// The value of a `text` attribute, which is left out of the payload.
+    ExcludedText,
+}
+
+impl PayloadItemKind {
+    /// Whether the item comes from the signing context rather than the bank content.
+    pub fn is_context(self) -> bool {
+        matches!(
+            self,
+            PayloadItemKind::AuthorHandle | PayloadItemKind::PlayerHandle | PayloadItemKind::BankName
+        )
+    }
+}
+
+impl fmt::Display for PayloadItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            PayloadItemKind::AuthorHandle => "author-handle",
+            PayloadItemKind::PlayerHandle => "player-handle",
+            PayloadItemKind::BankName => "bank-name",
+            PayloadItemKind::Section => "section",
+            PayloadItemKind::Key => "key",
+            PayloadItemKind::Tag => "tag",
+            PayloadItemKind::AttributeName => "attribute",
+            PayloadItemKind::AttributeValue => "value",
+            PayloadItemKind::ExcludedText => "excluded-text",
+        };
+        f.pad(label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadItem {
+    pub kind: PayloadItemKind,
+    pub text: String,
+}
+
+impl PayloadItem {
+    /// Whether the item is part of the signed payload.
+    pub fn is_signed(&self) -> bool {
+        self.kind != PayloadItemKind::ExcludedText
+    }
+}
+
+/// The signing payload of a bank, item by item, with the signature it hashes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub items: Vec<PayloadItem>,
+    pub signature: String,
+}
+
+impl Payload {
+    /// The exact bytes that are hashed.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.items
+            .iter()
+            .filter(|item| item.is_signed())
+            .flat_map(|item| item.text.bytes())
+            .collect()
+    }
+
+    /// The hashed bytes of the bank content: the payload without the context items
+    /// that lead it.
+    pub fn content_bytes(&self) -> Vec<u8> {
+        self.items
+            .iter()
+            .filter(|item| item.is_signed() && !item.kind.is_context())
+            .flat_map(|item| item.text.bytes())
+            .collect()
+    }
+}
+
+impl PayloadHasher {
+    /// Starts a payload with the context items that lead every signature.
+    pub fn new(context: &SigningContext) -> Self {
+        Self::start(context, None)
+    }
+
+    /// Like `new`, but also keeps every item so the payload can be inspected.
+    pub fn recording(context: &SigningContext) -> Self {
+        Self::start(context, Some(Vec::new()))
+    }
+
+    fn start(context: &SigningContext, items: Option<Vec<PayloadItem>>) -> Self {
+        let mut hasher = PayloadHasher {
+            hasher: Sha1::new(),
+            scratch: String::new(),
+            items,
+        };
+        hasher.item(PayloadItemKind::AuthorHandle, &context.author_handle.to_string());
+        hasher.item(PayloadItemKind::PlayerHandle, &context.player_handle.to_string());
+        hasher.item(PayloadItemKind::BankName, &context.bank_name);
+        hasher
+    }
+
+    pub fn item(&mut self, kind: PayloadItemKind, item: &str) {
+        if kind != PayloadItemKind::ExcludedText {
+            self.hasher.update(item.as_bytes());
+        }
+        if let Some(items) = self.items.as_mut() {
+            items.push(PayloadItem { kind, text: item.to_string() });
+        }
+    }
+
+    pub fn sections(&mut self, sections: &[Section]) {
+        for section in sections {
+            self.item(PayloadItemKind::Section, &section.name);
+            for key in &section.keys {
+                self.item(PayloadItemKind::Key, &key.name);
+                for value_element in &key.values {
+                    self.value_element(value_element);
+                }
+            }
+        }
+    }
+
+    pub fn value_element(&mut self, value_element: &ValueElement) {
+        self.item(PayloadItemKind::Tag, &value_element.tag_name);
+        if let [attribute] = value_element.attributes.as_slice() {
+            self.attribute(attribute);
+            return;
+        }
+        let mut attributes: Vec<&Attribute> = value_element.attributes.iter().collect();
+        attributes.sort_unstable_by(|a, b| a.name().cmp(b.name()));
+        for attribute in attributes {
+            self.attribute(attribute);
+        }
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        self.item(PayloadItemKind::AttributeName, attribute.name());
+        let kind = if attribute.is_text() {
+            PayloadItemKind::ExcludedText
+        } else {
+            PayloadItemKind::AttributeValue
+        };
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        let _ = attribute.write_value(&mut scratch);
+        self.item(kind, &scratch);
+        self.scratch = scratch;
+    }
+
+    /// The signature: the upper-case hex SHA-1 of the payload.
+    pub fn finish(self) -> String {
+        hex::encode_upper(self.hasher.finalize())
+    }
+
+    /// The signature along with the recorded items, empty unless `recording`.
+    pub fn finish_payload(mut self) -> Payload {
+        let items = self.items.take().unwrap_or_default();
+        Payload {
+            items,
+            signature: self.finish(),
+        }
+    }
+}
+
+/// The signature stored in a bank and the one computed for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankSignature {
+    pub computed: String,
+    pub stored: Option<String>,
+    /// Problems the parser found, if the bank needed the full parser.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BankSignature {
+    pub fn matches(&self) -> bool {
+        self.status() == VerificationStatus::Match
+    }
+
+    pub fn status(&self) -> VerificationStatus {
+        match &self.stored {
+            Some(stored) if *stored == self.computed => VerificationStatus::Match,
+            Some(_) => VerificationStatus::Mismatch,
+            None => VerificationStatus::MissingSignature,
+        }
+    }
+}
+
+impl fmt::Display for BankSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.computed)
+    }
+}
+
+/// Whether the signature stored in a bank is the one computed for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "kebab-case"))]
+pub enum VerificationStatus {
+    Match,
+    Mismatch,
+    /// The bank has no `Signature` tag, or one without a value.
+    MissingSignature,
+}
+
+impl fmt::Display for VerificationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationStatus::Match => f.pad("MATCH"),
+            VerificationStatus::Mismatch => f.pad("MISMATCH"),
+            VerificationStatus::MissingSignature => f.pad("MISSING"),
+        }
+    }
+}
+
+/// The result of verifying a bank: what it was signed with, both signatures, and the
+/// problems found while reading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VerificationReport {
+    /// The bank file, if the bank was read from one.
+    pub path: Option<PathBuf>,
+    pub context: SigningContext,
+    /// The signature stored in the bank.
+    pub file_signature: Option<String>,
+    pub computed_signature: String,
+    pub status: VerificationStatus,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl VerificationReport {
+    pub fn new(path: Option<PathBuf>, context: SigningContext, signature: BankSignature) -> Self {
+        VerificationReport {
+            path,
+            context,
+            status: signature.status(),
+            file_signature: signature.stored,
+            computed_signature: signature.computed,
+            diagnostics: signature.diagnostics,
+        }
+    }
+}
+
+/// Computes the signature of bank XML without building the document model.
+///
+/// Signs straight from the XML events, buffering only one key's values at a time to
+/// sort them. That relies on sections and keys appearing in sorted order, as the game
+/// writes them; banks that are unsorted, malformed or contain unparsable values are
+/// handed to the full parser instead, which also reports their problems.
+pub fn sign_str(source: &str, context: &SigningContext, mode: ParseMode) -> AppResult<BankSignature> {
+    match stream_signature(source, context) {
+        Some(signature) => Ok(signature),
+        None => {
+            let bank = BankParser::from_reader_with_mode(source.as_bytes(), context.clone(), mode)?;
+            Ok(BankSignature {
+                computed: bank.signature,
+                stored: bank.current_signature,
+                diagnostics: bank.diagnostics,
+            })
+        }
+    }
+}
+
+/// Streaming fast path of `sign_str`. `None` when the bank needs the full parser.
+fn stream_signature(source: &str, context: &SigningContext) -> Option<BankSignature> {
+    let mut hasher = PayloadHasher::new(context);
+    let mut stored = None;
+    let mut signature_found = false;
+    // Nesting depth of the current element, `<Bank>` being 1.
+    let mut depth = 0;
+
+    let mut section: Option<String> = None;
+    let mut section_signed = false;
+    let mut last_section: Option<String> = None;
+    let mut last_key: Option<String> = None;
+    let mut key: Option<(String, Vec<ValueElement>)> = None;
+
+    for event in EventReader::new(source.as_bytes()) {
+        match event.ok()? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                depth += 1;
+                let tag_name = qualified_name(&name);
+                match tag_name.as_str() {
+                    SECTION_TAG => {
+                        if section.is_some() {
+                            return None;
+                        }
+                        section = Some(name_attribute(&attributes)?);
+                        section_signed = false;
+                        last_key = None;
+                    }
+                    KEY_TAG => {
+                        if key.is_some() || section.is_none() {
+                            return None;
+                        }
+                        key = Some((name_attribute(&attributes)?, Vec::new()));
+                    }
+                    SIGNATURE_TAG => {
+                        // Misplaced and repeated signatures are reported by the full parser.
+                        if depth != 2 || signature_found {
+                            return None;
+                        }
+                        signature_found = true;
+                        stored = attributes
+                            .iter()
+                            .find(|attr| is_unprefixed(attr, "value"))
+                            .map(|attr| attr.value.clone());
+                    }
+                    _ => {
+                        if let Some((_, values)) = key.as_mut() {
+                            let attributes = attributes
+                                .iter()
+                                .map(|attr| Attribute::parse(&qualified_name(&attr.name), &attr.value).ok())
+                                .collect::<Option<Vec<_>>>()?;
+                            values.push(ValueElement { tag_name, attributes });
+                        }
+                    }
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                depth -= 1;
+                match qualified_name(&name).as_str() {
+                    KEY_TAG => {
+                        let (key_name, mut values) = key.take()?;
+                        if values.is_empty() {
+                            continue;
+                        }
+                        let section_name = section.as_ref()?;
+                        if !section_signed {
+                            if last_section.as_ref().is_some_and(|last| last > section_name) {
+                                return None;
+                            }
+                            hasher.item(PayloadItemKind::Section, section_name);
+                            last_section = Some(section_name.clone());
+                            section_signed = true;
+                        }
+                        if last_key.as_ref().is_some_and(|last| *last > key_name) {
+                            return None;
+                        }
+                        values.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+                        hasher.item(PayloadItemKind::Key, &key_name);
+                        for value_element in &values {
+                            hasher.value_element(value_element);
+                        }
+                        last_key = Some(key_name);
+                    }
+                    SECTION_TAG => {
+                        section = None;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(BankSignature {
+        computed: hasher.finish(),
+        stored,
+        diagnostics: Vec::new(),
+    })
+}
+
+fn name_attribute(attributes: &[OwnedAttribute]) -> Option<String> {
+    attributes
+        .iter()
+        .find(|attr| is_unprefixed(attr, NAME_ATTRIBUTE))
+        .map(|attr| attr.value.clone())
+}
+
+fn is_unprefixed(attribute: &OwnedAttribute, name: &str) -> bool {
+    attribute.name.prefix.is_none() && attribute.name.local_name == name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank_parser::attribute::Attribute;
+
+    fn context(bank_name: &str) -> SigningContext {
+        SigningContext::new(bank_name, "1-S2-1-1234567".parse().unwrap(), "2-S2-1-7654321".parse().unwrap())
+    }
+
+    /// Fixed values whose text is not what formatting their 20.12 value gives.
+    const FIXED_BANK: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Bank version="1">
+    <Section name="Stats">
+        <Key name="Fine"><Value fixed="0.00012"/></Key>
+        <Key name="Half"><Value fixed="1.5"/></Key>
+        <Key name="Neg"><Value fixed="-3.25"/></Key>
+        <Key name="Odd"><Value fixed="0.2999"/></Key>
+        <Key name="Whole"><Value fixed="7"/></Key>
+    </Section>
+    <Signature value="0"/>
+</Bank>
+"#;
+    /// What the tool computed for `FIXED_BANK` before fixed values were read as 20.12.
+    const FIXED_BANK_SIGNATURE: &str = "E6ED83355AA5EACE8B015E8F46126578D4D47E6E";
+
+    #[test]
+    fn fixed_values_are_signed_as_written() {
+        let bank = BankParser::from_str(FIXED_BANK, context("Fixed")).unwrap();
+        assert_eq!(bank.signature, FIXED_BANK_SIGNATURE);
+        let streamed = sign_str(FIXED_BANK, &context("Fixed"), ParseMode::Strict).unwrap();
+        assert_eq!(streamed.computed, FIXED_BANK_SIGNATURE);
+
+        let odd = bank.value("Stats", "Odd").unwrap();
+        assert_eq!(odd.value(), "0.2999");
+        assert_eq!(odd.as_fixed().map(|fixed| fixed.raw()), Some(1228));
+        assert_eq!(bank.value("Stats", "Half"), Some(&Attribute::Fixed("1.5".parse().unwrap(), None)));
+    }
+
+    /// Signs `source` with both signers, checking that the streaming one agrees with the
+    /// full parser wherever it does not hand the bank over to it. Returns whether it did.
+    fn assert_signers_agree(source: &str) -> bool {
+        let context = context("Bank");
+        let bank = BankParser::from_reader_with_mode(source.as_bytes(), context.clone(), ParseMode::Lenient).unwrap();
+        let signed = sign_str(source, &context, ParseMode::Lenient).unwrap();
+        assert_eq!(signed.computed, bank.signature, "{}", source);
+        assert_eq!(signed.stored, bank.current_signature, "{}", source);
+        match stream_signature(source, &context) {
+            Some(streamed) => {
+                assert_eq!(streamed.computed, bank.signature, "{}", source);
+                assert_eq!(streamed.stored, bank.current_signature, "{}", source);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[test]
+    fn signers_agree_on_sorted_banks() {
+        assert!(assert_signers_agree(FIXED_BANK));
+        assert!(assert_signers_agree(
+            r#"<Bank version="1">
+    <Section name="A">
+        <Key name="Empty"/>
+        <Key name="Gold"><Value int="7"/></Key>
+        <Key name="Name"><Value string="Raynor"/></Key>
+    </Section>
+    <Section name="Empty"/>
+    <Section name="Options"><Key name="Music"><Value flag="1"/></Key></Section>
+    <Signature value="ABC"/>
+</Bank>"#
+        ));
+        assert!(assert_signers_agree(
+            r#"<Bank version="1"><Section name="A"><Key name="K"><Value int="1"/></Key></Section></Bank>"#
+        ));
+    }
+
+    #[test]
+    fn signers_agree_on_unsorted_banks() {
+        let sections = r#"<Bank version="1">
+    <Section name="B"><Key name="K"><Value int="1"/></Key></Section>
+    <Section name="A"><Key name="K"><Value int="2"/></Key></Section>
+</Bank>"#;
+        assert!(!assert_signers_agree(sections));
+        let keys = r#"<Bank version="1">
+    <Section name="A"><Key name="K2"><Value int="1"/></Key><Key name="K1"><Value int="2"/></Key></Section>
+</Bank>"#;
+        assert!(!assert_signers_agree(keys));
+        let values = r#"<Bank version="1">
+    <Section name="A"><Key name="K"><Value int="1"/><Flags flag="1"/><Extra string="x"/></Key></Section>
+</Bank>"#;
+        assert!(assert_signers_agree(values));
+    }
+
+    #[test]
+    fn signers_agree_on_text_attributes() {
+        assert!(assert_signers_agree(
+            r#"<Bank version="1">
+    <Section name="A">
+        <Key name="Note"><Value text="left out of the signature"/></Key>
+        <Key name="Title"><Value string="x" text="also left out"/></Key>
+    </Section>
+</Bank>"#
+        ));
+    }
+
+    #[test]
+    fn signers_agree_on_nested_and_duplicate_signatures() {
+        let nested = r#"<Bank version="1">
+    <Section name="A"><Key name="K"><Signature value="NESTED"/><Value int="1"/></Key></Section>
+    <Signature value="ABC"/>
+</Bank>"#;
+        assert!(!assert_signers_agree(nested));
+        let duplicate = r#"<Bank version="1">
+    <Section name="A"><Key name="K"><Value int="1"/></Key></Section>
+    <Signature value="ABC"/>
+    <Signature value="DEF"/>
+</Bank>"#;
+        assert!(!assert_signers_agree(duplicate));
+    }
+
+    #[test]
+    fn signers_agree_on_cdata_and_entities() {
+        assert!(assert_signers_agree(
+            r#"<Bank version="1">
+    <Section name="A&amp;B">
+        <Key name="&lt;K&gt;"><Value string="&quot;Jim&apos;s&quot; &#x263A; &#65;"/></Key>
+        <Key name="Script"><![CDATA[<Value int="1"/>]]><Value int="2"/></Key>
+    </Section>
+    <!-- <Signature value="COMMENTED"/> -->
+    <Signature value="ABC"/>
+</Bank>"#
+        ));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::bank_parser::diagnostic::Span;
+use xml::common::TextPosition;
+
+pub(crate) const UTF8_BOM: &str = "\u{FEFF}";
+
+/// Translates xml-rs reader positions (row and column in characters) back into byte
+/// offsets of the source text they were read from.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(source: &str) -> Self {
+        // The reader skips a leading BOM without counting it as a column.
+        let first_line = if source.starts_with(UTF8_BOM) { UTF8_BOM.len() } else { 0 };
+        let line_starts = std::iter::once(first_line)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        SourceMap { line_starts }
+    }
+
+    /// Byte offset of `position` in `source`, clamped to the end of the source.
+    pub fn offset(&self, source: &str, position: TextPosition) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.row as usize) else {
+            return source.len();
+        };
+        source[line_start..]
+            .char_indices()
+            .nth(position.column as usize)
+            .map(|(index, _)| line_start + index)
+            .unwrap_or(source.len())
+    }
+
+    /// Span of the byte range `start..end`.
+    pub fn span(&self, source: &str, start: usize, end: usize) -> Span {
+        let line_index = self.line_starts.partition_point(|&line_start| line_start <= start).saturating_sub(1);
+        let line_start = self.line_starts[line_index].min(start);
+        Span {
+            start,
+            end,
+            line: line_index + 1,
+            column: source[line_start..start].chars().count() + 1,
+        }
+    }
+
+    /// Span of the single character at `position`.
+    pub fn span_at(&self, source: &str, position: TextPosition) -> Span {
+        let start = self.offset(source, position);
+        let end = source[start..].chars().next().map_or(start, |c| start + c.len_utf8());
+        self.span(source, start, end)
+    }
+}
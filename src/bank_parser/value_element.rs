@@ -1,6 +1,7 @@
 use crate::bank_parser::attribute::Attribute;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ValueElement {
     pub tag_name: String,
     pub attributes: Vec<Attribute>,
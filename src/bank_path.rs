@@ -1,5 +1,7 @@
 use std::fmt;
-use crate::{AppError, AppResult, Args};
+use crate::handle::{AuthorHandle, HandleError, PlayerHandle};
+use crate::signing_context::SigningContext;
+use crate::{AppError, AppResult, BankArgs};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, thiserror::Error, Clone)]
@@ -12,6 +14,12 @@ pub enum BankPathError {
     MissingAuthorHandle(PathBuf),
     #[error("Could not determine Player Handle from path structure near '{0}'")]
     MissingPlayerHandle(PathBuf),
+    #[error("Author Handle directory '{0}' is not a handle: {1}")]
+    InvalidAuthorHandle(PathBuf, HandleError),
+    #[error("Player Handle directory '{0}' is not a handle: {1}")]
+    InvalidPlayerHandle(PathBuf, HandleError),
+    #[error("Could not determine the account directory from path structure near '{0}'")]
+    MissingAccountDir(PathBuf),
     #[error("Invalid UTF-8 encoding in path component near '{0}'")]
     InvalidPathEncoding(PathBuf),
 }
@@ -21,17 +29,24 @@ pub enum BankPathError {
 pub struct BankPath {
     pub full_path: PathBuf,
     pub bank_name: String,
-    pub author_handle: String,
-    pub player_handle: String,
+    pub author_handle: AuthorHandle,
+    pub player_handle: PlayerHandle,
 }
 
 impl BankPath {
-    pub fn new(args: &Args) -> AppResult<Self> {
+    pub fn new(args: &BankArgs) -> AppResult<Self> {
         let path = PathBuf::from(&args.bank_path);
 
         if !path.is_file() {
             return Err(AppError::BankPathError(BankPathError::BankNotFound(path)));
         }
+        Self::for_new_bank(args)
+    }
+
+    /// Derives the metadata for a bank that does not have to exist yet, such as the
+    /// destination of an import.
+    pub fn for_new_bank(args: &BankArgs) -> AppResult<Self> {
+        let path = PathBuf::from(&args.bank_path);
         let canonical_path = path.canonicalize().unwrap_or(path.clone());
 
 
@@ -47,29 +62,44 @@ impl BankPath {
 
 
         // --- Handle Extraction Helper ---
-        // Extracts parent dir name as String, handling potential errors
-        let get_parent_dir_name = |p: &Path, err_missing: fn(PathBuf) -> BankPathError| -> Result<String, BankPathError> {
+        // The name of the directory `p` is in, or `err_missing` if there is none
+        let get_parent_dir = |p: &Path, err_missing: fn(PathBuf) -> BankPathError| -> Result<PathBuf, BankPathError> {
             p.parent()
-                .and_then(|parent| parent.file_name())
+                .filter(|parent| parent.file_name().is_some())
+                .map(Path::to_path_buf)
+                .ok_or_else(|| err_missing(p.to_path_buf())) // Use closure for lazy path cloning
+        };
+        let dir_name = |dir: &Path| -> Result<String, BankPathError> {
+            dir.file_name()
                 .and_then(|name| name.to_str())
                 .map(|s| s.to_string())
-                .ok_or_else(|| err_missing(p.to_path_buf())) // Use closure for lazy path cloning
+                .ok_or_else(|| BankPathError::InvalidPathEncoding(dir.to_path_buf()))
         };
 
         // --- Author Handle Extraction ---
-        let author_handle = match &args.player_handle {
-            Some(handle) => handle.clone(),
-            None => get_parent_dir_name(&path, BankPathError::MissingPlayerHandle)?
+        // Banks/<author handle>/<bank>.SC2Bank
+        let author_handle = match &args.author_handle {
+            Some(handle) => *handle,
+            None => {
+                let author_dir = get_parent_dir(&path, BankPathError::MissingAuthorHandle)?;
+                dir_name(&author_dir)?
+                    .parse()
+                    .map_err(|err| BankPathError::InvalidAuthorHandle(author_dir, err))?
+            }
         };
 
         // --- Player Handle Extraction ---
-        let player_handle = match &args.author_handle {
-            Some(handle) => handle.clone(),
+        // <player handle>/Banks/<author handle>/<bank>.SC2Bank
+        let player_handle = match &args.player_handle {
+            Some(handle) => *handle,
             None => {
-                let parent_path = path.parent()
-                    .and_then(|p|p.parent())
-                    .ok_or_else(|| BankPathError::MissingAuthorHandle(path.clone()))?;
-                get_parent_dir_name(parent_path, BankPathError::MissingAuthorHandle)?
+                let banks_dir = path.parent()
+                    .and_then(|p| p.parent())
+                    .ok_or_else(|| BankPathError::MissingPlayerHandle(path.clone()))?;
+                let player_dir = get_parent_dir(banks_dir, BankPathError::MissingPlayerHandle)?;
+                dir_name(&player_dir)?
+                    .parse()
+                    .map_err(|err| BankPathError::InvalidPlayerHandle(player_dir, err))?
             }
         };
 
@@ -81,6 +111,20 @@ impl BankPath {
             player_handle,
         })
     }
+
+    /// The account directory the bank is in: `<account>/<player>/Banks/<author>/<bank>`.
+    pub fn account_dir(&self) -> Result<&Path, BankPathError> {
+        self.full_path
+            .ancestors()
+            .nth(4)
+            .filter(|dir| dir.file_name().is_some())
+            .ok_or_else(|| BankPathError::MissingAccountDir(self.full_path.clone()))
+    }
+
+    /// Returns the signing metadata derived from this path.
+    pub fn context(&self) -> SigningContext {
+        SigningContext::new(&self.bank_name, self.author_handle, self.player_handle)
+    }
 }
 
 impl fmt::Display for BankPath {
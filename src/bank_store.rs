@@ -0,0 +1,254 @@
+use crate::bank_path::BankPath;
+use crate::batch::is_bank_file;
+use crate::handle::{AuthorHandle, PlayerHandle};
+use crate::AppResult;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ACCOUNTS_DIR: &str = "Accounts";
+const BANKS_DIR: &str = "Banks";
+
+/// The banks of a StarCraft II `Accounts` directory.
+///
+/// The game keeps banks at `Accounts/<account id>/<player handle>/Banks/<author
+/// handle>/<bank name>.SC2Bank`. Directories that do not fit that layout, such as the
+/// hotkey folders next to the player handles or directories whose names are not
+/// handles, are skipped. Everything is listed in name order.
+#[derive(Debug, Clone)]
+pub struct BankStore {
+    pub root: PathBuf,
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub id: String,
+    pub path: PathBuf,
+    pub players: Vec<Player>,
+}
+
+/// A player handle directory of an account.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub handle: PlayerHandle,
+    pub path: PathBuf,
+    pub authors: Vec<Author>,
+}
+
+/// The banks one map author keeps for a player.
+#[derive(Debug, Clone)]
+pub struct Author {
+    pub handle: AuthorHandle,
+    pub path: PathBuf,
+    pub banks: Vec<BankPath>,
+}
+
+impl BankStore {
+    /// Reads the store at `root`, which is either the `Accounts` directory itself or
+    /// the StarCraft II documents directory containing it.
+    pub fn open(root: impl AsRef<Path>) -> AppResult<Self> {
+        let root = root.as_ref();
+        let root = match root.join(ACCOUNTS_DIR) {
+            accounts if accounts.is_dir() => accounts,
+            _ => root.to_path_buf(),
+        };
+
+        let mut accounts = Vec::new();
+        for (id, path) in subdirectories(&root)? {
+            let players = read_players(&path)?;
+            accounts.push(Account { id, path, players });
+        }
+        Ok(BankStore { root, accounts })
+    }
+
+    /// Every bank in the store.
+    pub fn banks(&self) -> impl Iterator<Item = &BankPath> {
+        self.accounts
+            .iter()
+            .flat_map(|account| &account.players)
+            .flat_map(|player| &player.authors)
+            .flat_map(|author| &author.banks)
+    }
+}
+
+/// Where the game keeps the bank `bank_name` of `author` for `player` in the
+/// account directory `account`.
+pub fn bank_location(account: &Path, player: PlayerHandle, author: AuthorHandle, bank_name: &str) -> PathBuf {
+    account
+        .join(player.to_string())
+        .join(BANKS_DIR)
+        .join(author.to_string())
+        .join(format!("{}.SC2Bank", bank_name))
+}
+
+/// The handles of the players in the account directory `account`, whether or not they
+/// have banks yet.
+pub fn player_handles(account: &Path) -> AppResult<Vec<PlayerHandle>> {
+    Ok(subdirectories(account)?
+        .into_iter()
+        .filter_map(|(name, _)| name.parse().ok())
+        .collect())
+}
+
+fn read_players(account: &Path) -> AppResult<Vec<Player>> {
+    let mut players = Vec::new();
+    for (name, path) in subdirectories(account)? {
+        let banks_dir = path.join(BANKS_DIR);
+        let Ok(handle) = name.parse::<PlayerHandle>() else {
+            continue;
+        };
+        if !banks_dir.is_dir() {
+            continue;
+        }
+        let mut authors = Vec::new();
+        for (author_name, author_path) in subdirectories(&banks_dir)? {
+            let Ok(author_handle) = author_name.parse::<AuthorHandle>() else {
+                continue;
+            };
+            let banks = read_banks(&author_path, author_handle, handle)?;
+            authors.push(Author { handle: author_handle, path: author_path, banks });
+        }
+        players.push(Player { handle, path, authors });
+    }
+    Ok(players)
+}
+
+fn read_banks(directory: &Path, author_handle: AuthorHandle, player_handle: PlayerHandle) -> AppResult<Vec<BankPath>> {
+    let mut banks = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() || !is_bank_file(&path) {
+            continue;
+        }
+        let Some(bank_name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        banks.push(BankPath {
+            bank_name: bank_name.to_string(),
+            author_handle,
+            player_handle,
+            full_path: path,
+        });
+    }
+    banks.sort_by(|a, b| a.bank_name.cmp(&b.bank_name));
+    Ok(banks)
+}
+
+/// The directories in `directory` with UTF-8 names, by name.
+fn subdirectories(directory: &Path) -> AppResult<Vec<(String, PathBuf)>> {
+    let mut directories = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            directories.push((name.to_string(), path));
+        }
+    }
+    directories.sort();
+    Ok(directories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: &str = "2-S2-1-7654321";
+    const AUTHOR: &str = "1-S2-1-1234567";
+
+    /// A scratch StarCraft II documents directory, removed when dropped.
+    struct Documents {
+        root: PathBuf,
+    }
+
+    impl Documents {
+        /// Holds the files `paths`, relative to `Accounts`.
+        fn new(test: &str, paths: &[&str]) -> Self {
+            let root = std::env::temp_dir().join(format!("sc2_bank_signer-bank_store-{}-{}", std::process::id(), test));
+            let _ = fs::remove_dir_all(&root);
+            for path in paths {
+                let path = root.join(ACCOUNTS_DIR).join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+            Documents { root }
+        }
+    }
+
+    impl Drop for Documents {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn bank(player: &str, author: &str, name: &str) -> String {
+        format!("111/{}/Banks/{}/{}.SC2Bank", player, author, name)
+    }
+
+    #[test]
+    fn lists_the_banks_of_every_player_and_author() {
+        let documents = Documents::new(
+            "open",
+            &[
+                &bank(PLAYER, AUTHOR, "Second"),
+                &bank(PLAYER, AUTHOR, "First"),
+                &bank(PLAYER, "1-S2-1-1", "Other"),
+                &bank("2-S2-1-1", AUTHOR, "Bank"),
+                &format!("111/{}/Banks/{}/notes.txt", PLAYER, AUTHOR),
+                // Not handles, or not where the game keeps banks.
+                &format!("111/{}/Banks/Cache/Bank.SC2Bank", PLAYER),
+                "111/Hotkeys/Banks/1-S2-1-1/Bank.SC2Bank",
+                &format!("111/{}/Bank.SC2Bank", PLAYER),
+                "222/Hotkeys/Default.SC2Hotkeys",
+            ],
+        );
+        let store = BankStore::open(&documents.root).unwrap();
+        assert_eq!(store.root, documents.root.join(ACCOUNTS_DIR));
+        let accounts: Vec<&str> = store.accounts.iter().map(|account| account.id.as_str()).collect();
+        assert_eq!(accounts, ["111", "222"]);
+        assert!(store.accounts[1].players.is_empty());
+
+        let players: Vec<String> = store.accounts[0].players.iter().map(|player| player.handle.to_string()).collect();
+        assert_eq!(players, ["2-S2-1-1", PLAYER]);
+        let authors: Vec<String> =
+            store.accounts[0].players[1].authors.iter().map(|author| author.handle.to_string()).collect();
+        assert_eq!(authors, ["1-S2-1-1", AUTHOR]);
+
+        let banks: Vec<String> = store
+            .banks()
+            .map(|bank| format!("{}/{}/{}", bank.player_handle, bank.author_handle, bank.bank_name))
+            .collect();
+        assert_eq!(
+            banks,
+            [
+                format!("2-S2-1-1/{}/Bank", AUTHOR),
+                format!("{}/1-S2-1-1/Other", PLAYER),
+                format!("{}/{}/First", PLAYER, AUTHOR),
+                format!("{}/{}/Second", PLAYER, AUTHOR),
+            ]
+        );
+        let first = store.banks().nth(2).unwrap();
+        assert_eq!(first.full_path, store.root.join(bank(PLAYER, AUTHOR, "First")));
+    }
+
+    #[test]
+    fn opens_the_accounts_directory_itself() {
+        let documents = Documents::new("accounts", &[&bank(PLAYER, AUTHOR, "Bank")]);
+        let store = BankStore::open(documents.root.join(ACCOUNTS_DIR)).unwrap();
+        assert_eq!(store.root, documents.root.join(ACCOUNTS_DIR));
+        assert_eq!(store.banks().count(), 1);
+        assert!(BankStore::open(documents.root.join("missing")).is_err());
+    }
+
+    #[test]
+    fn locates_banks_where_the_store_lists_them() {
+        let documents = Documents::new("location", &[&bank(PLAYER, AUTHOR, "Bank"), "111/Hotkeys/x"]);
+        let store = BankStore::open(&documents.root).unwrap();
+        let account = &store.accounts[0];
+        let listed = store.banks().next().unwrap();
+        let location = bank_location(&account.path, listed.player_handle, listed.author_handle, &listed.bank_name);
+        assert_eq!(location, listed.full_path);
+        assert_eq!(player_handles(&account.path).unwrap(), [PLAYER.parse().unwrap()]);
+    }
+}
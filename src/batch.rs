@@ -0,0 +1,298 @@
+use crate::backup::BackupPolicy;
+use crate::bank_parser::signature::{sign_str, VerificationReport, VerificationStatus};
+use crate::bank_parser::{BankParser, ParseMode};
+use crate::bank_path::BankPath;
+use crate::{AppError, AppResult, BankArgs};
+use log::debug;
+use rayon::prelude::*;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BANK_EXTENSION: &str = "SC2Bank";
+
+/// How verifying one bank of a batch turned out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchStatus {
+    Match,
+    Mismatch,
+    MissingSignature,
+    /// Mismatched or unsigned, and the computed signature was written to the file.
+    Resigned,
+    Error(String),
+}
+
+impl BatchStatus {
+    /// The status in machine-readable output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchStatus::Match => "match",
+            BatchStatus::Mismatch => "mismatch",
+            BatchStatus::MissingSignature => "missing-signature",
+            BatchStatus::Resigned => "resigned",
+            BatchStatus::Error(_) => "error",
+        }
+    }
+}
+
+impl fmt::Display for BatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchStatus::Match => f.pad("MATCH"),
+            BatchStatus::Mismatch => f.pad("MISMATCH"),
+            BatchStatus::MissingSignature => f.pad("MISSING"),
+            BatchStatus::Resigned => f.pad("RESIGNED"),
+            BatchStatus::Error(_) => f.pad("ERROR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchEntry {
+    pub path: PathBuf,
+    /// `None` if the signing metadata could not be derived from the path.
+    pub bank_path: Option<BankPath>,
+    pub status: BatchStatus,
+    /// `None` if the bank could not be read.
+    pub report: Option<VerificationReport>,
+}
+
+/// Serializes as one flat object: the path, the context, both signatures, the status
+/// name, the diagnostics and, for errors, the error message. Fields that are unknown
+/// because of an error are `null`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for BatchEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let report = self.report.as_ref();
+        let context = report
+            .map(|report| report.context.clone())
+            .or_else(|| self.bank_path.as_ref().map(BankPath::context));
+        let error = match &self.status {
+            BatchStatus::Error(message) => Some(message),
+            _ => None,
+        };
+        let mut entry = serializer.serialize_struct("BatchEntry", 7)?;
+        entry.serialize_field("path", &self.path)?;
+        entry.serialize_field("context", &context)?;
+        entry.serialize_field("file_signature", &report.and_then(|report| report.file_signature.as_ref()))?;
+        entry.serialize_field("computed_signature", &report.map(|report| &report.computed_signature))?;
+        entry.serialize_field("status", self.status.name())?;
+        entry.serialize_field("diagnostics", report.map_or(&[][..], |report| &report.diagnostics))?;
+        entry.serialize_field("error", &error)?;
+        entry.end()
+    }
+}
+
+/// Expands files, directories and glob patterns into the bank files they contain.
+///
+/// Files are taken as given; directories, including those a pattern matches, are
+/// searched recursively for `.SC2Bank` files. Links to directories found while
+/// searching are not followed, as one pointing up the tree would never end the search.
+/// The result is sorted and free of duplicates.
+pub fn find_banks(inputs: &[String]) -> AppResult<Vec<PathBuf>> {
+    let mut banks = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.exists() {
+            collect_banks(path, true, &mut banks)?;
+        } else if is_pattern(input) {
+            for entry in glob::glob(input)? {
+                collect_banks(&entry?, false, &mut banks)?;
+            }
+        } else {
+            return Err(AppError::FileNotFound(path.to_path_buf()));
+        }
+    }
+    banks.sort();
+    banks.dedup();
+    Ok(banks)
+}
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+pub fn is_bank_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(BANK_EXTENSION))
+}
+
+/// Adds `path` if it is a bank, or the banks below it if it is a directory. Files that
+/// are not banks are only taken when named `explicitly`.
+fn collect_banks(path: &Path, explicitly: bool, banks: &mut Vec<PathBuf>) -> AppResult<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_symlink() && path.is_dir() {
+                debug!("Not following the directory link {}", path.display());
+                continue;
+            }
+            collect_banks(&path, false, banks)?;
+        }
+    } else if explicitly || is_bank_file(path) {
+        banks.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Verifies every bank in parallel, each with the context derived from its own path and
+/// the overrides of `overrides`. With `write`, mismatched and unsigned banks are
+/// re-signed, backed up according to `backup`.
+pub fn verify_batch(banks: &[PathBuf], overrides: &BankArgs, write: bool, backup: &BackupPolicy) -> Vec<BatchEntry> {
+    banks
+        .par_iter()
+        .map(|path| verify_path(path, overrides, write, backup))
+        .collect()
+}
+
+/// Verifies the bank at `path` with the context derived from it and the overrides of
+/// `overrides`, as one entry of a batch.
+pub fn verify_path(path: &Path, overrides: &BankArgs, write: bool, backup: &BackupPolicy) -> BatchEntry {
+    let args = BankArgs {
+        bank_path: path.display().to_string(),
+        ..overrides.clone()
+    };
+    match BankPath::new(&args) {
+        Ok(bank_path) => check_bank(&bank_path, args.parse.mode(), write, backup),
+        Err(err) => BatchEntry {
+            path: path.to_path_buf(),
+            bank_path: None,
+            status: BatchStatus::Error(err.to_string()),
+            report: None,
+        },
+    }
+}
+
+/// The error on one line, with the reader's message for XML errors, which are
+/// otherwise rendered against the source.
+fn describe(err: &AppError) -> String {
+    match err {
+        AppError::XmlReaderError(xml_error) => format!("{}: {}", err, xml_error),
+        _ => err.to_string(),
+    }
+}
+
+/// Verifies one bank, re-signing it on a mismatch or missing signature if `write` is set.
+pub fn check_bank(bank_path: &BankPath, mode: ParseMode, write: bool, backup: &BackupPolicy) -> BatchEntry {
+    let (status, report) = match verify_one(bank_path, mode, write, backup) {
+        Ok((status, report)) => (status, Some(report)),
+        Err(err) => (BatchStatus::Error(describe(&err)), None),
+    };
+    BatchEntry {
+        path: bank_path.full_path.clone(),
+        bank_path: Some(bank_path.clone()),
+        status,
+        report,
+    }
+}
+
+fn verify_one(
+    bank_path: &BankPath,
+    mode: ParseMode,
+    write: bool,
+    backup: &BackupPolicy,
+) -> AppResult<(BatchStatus, VerificationReport)> {
+    let source = fs::read_to_string(&bank_path.full_path)?;
+    let context = bank_path.context();
+    let signature = sign_str(&source, &context, mode)?;
+    let report = VerificationReport::new(Some(bank_path.full_path.clone()), context, signature);
+    let status = match report.status {
+        VerificationStatus::Match => BatchStatus::Match,
+        _ if write => {
+            let mut bank = BankParser::from_bank_path(bank_path, mode)?;
+            bank.backup = backup.clone();
+            bank.replace_signature()?;
+            BatchStatus::Resigned
+        }
+        VerificationStatus::Mismatch => BatchStatus::Mismatch,
+        VerificationStatus::MissingSignature => BatchStatus::MissingSignature,
+    };
+    Ok((status, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory holding the files `names`, removed when dropped.
+    struct Tree {
+        root: PathBuf,
+    }
+
+    impl Tree {
+        fn new(test: &str, names: &[&str]) -> Self {
+            let root = std::env::temp_dir().join(format!("sc2_bank_signer-batch-{}-{}", std::process::id(), test));
+            let _ = fs::remove_dir_all(&root);
+            for name in names {
+                let path = root.join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+            Tree { root }
+        }
+
+        fn input(&self, relative: &str) -> String {
+            self.root.join(relative).display().to_string()
+        }
+
+        fn find(&self, inputs: &[&str]) -> Vec<String> {
+            let inputs: Vec<String> = inputs.iter().map(|input| self.input(input)).collect();
+            let banks = find_banks(&inputs).unwrap();
+            banks
+                .iter()
+                .map(|bank| bank.strip_prefix(&self.root).unwrap().display().to_string())
+                .collect()
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    const BANKS: &[&str] = &["a/One.SC2Bank", "a/b/Two.sc2bank", "a/b/notes.txt", "c/Three.SC2Bank", "Four.SC2Bank"];
+
+    #[test]
+    fn searches_directories_for_banks() {
+        let tree = Tree::new("dirs", BANKS);
+        assert_eq!(tree.find(&["a"]), ["a/One.SC2Bank", "a/b/Two.sc2bank"]);
+        assert_eq!(
+            tree.find(&[""]),
+            ["Four.SC2Bank", "a/One.SC2Bank", "a/b/Two.sc2bank", "c/Three.SC2Bank"]
+        );
+        assert_eq!(tree.find(&["a/b/notes.txt", "a/b"]), ["a/b/Two.sc2bank", "a/b/notes.txt"]);
+        assert_eq!(tree.find(&["a", "a/One.SC2Bank"]), ["a/One.SC2Bank", "a/b/Two.sc2bank"]);
+    }
+
+    #[test]
+    fn expands_glob_patterns() {
+        let tree = Tree::new("glob", BANKS);
+        assert_eq!(tree.find(&["*.SC2Bank"]), ["Four.SC2Bank"]);
+        assert_eq!(tree.find(&["*/b"]), ["a/b/Two.sc2bank"]);
+        assert_eq!(tree.find(&["[ac]"]), ["a/One.SC2Bank", "a/b/Two.sc2bank", "c/Three.SC2Bank"]);
+        assert_eq!(tree.find(&["a/b/*"]), ["a/b/Two.sc2bank"]);
+        assert!(tree.find(&["x*"]).is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_paths() {
+        let tree = Tree::new("missing", BANKS);
+        let err = find_banks(&[tree.input("x")]).unwrap_err();
+        assert!(matches!(err, AppError::FileNotFound(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_follow_directory_links() {
+        let tree = Tree::new("links", BANKS);
+        std::os::unix::fs::symlink(&tree.root, tree.root.join("a/b/up")).unwrap();
+        std::os::unix::fs::symlink(tree.root.join("c"), tree.root.join("a/c")).unwrap();
+        std::os::unix::fs::symlink(tree.root.join("c/Three.SC2Bank"), tree.root.join("a/Linked.SC2Bank")).unwrap();
+        assert_eq!(tree.find(&["a"]), ["a/Linked.SC2Bank", "a/One.SC2Bank", "a/b/Two.sc2bank"]);
+        assert_eq!(tree.find(&["a/c"]), ["a/c/Three.SC2Bank"]);
+    }
+}
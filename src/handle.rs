@@ -0,0 +1,327 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+const GAME: &str = "S2";
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+pub enum HandleError {
+    #[error("Invalid handle '{0}': expected REGION-S2-REALM-ID, e.g. 1-S2-1-1234567")]
+    Malformed(String),
+    #[error("Invalid handle '{handle}': unknown region {region}")]
+    UnknownRegion { handle: String, region: String },
+    #[error("Invalid handle range '{0}': the last id is below the first")]
+    EmptyRange(String),
+}
+
+/// The Battle.net region a handle belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Region {
+    Us,
+    Eu,
+    Kr,
+    Cn,
+    /// The public test realm.
+    Ptr,
+}
+
+impl Region {
+    /// The number the region is written as in a handle.
+    pub fn code(self) -> u32 {
+        match self {
+            Region::Us => 1,
+            Region::Eu => 2,
+            Region::Kr => 3,
+            Region::Cn => 5,
+            Region::Ptr => 98,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(Region::Us),
+            2 => Some(Region::Eu),
+            3 => Some(Region::Kr),
+            5 => Some(Region::Cn),
+            98 => Some(Region::Ptr),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Region::Us => "US",
+            Region::Eu => "EU",
+            Region::Kr => "KR",
+            Region::Cn => "CN",
+            Region::Ptr => "PTR",
+        };
+        f.pad(name)
+    }
+}
+
+/// A StarCraft II handle such as `2-S2-1-1234567`: region, game, realm and id.
+///
+/// Handles are signed as text, so only the canonical form is accepted: numbers without
+/// sign or leading zeros. That way a parsed handle always displays as the text it was
+/// parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Handle {
+    pub region: Region,
+    pub realm: u32,
+    pub id: u64,
+}
+
+impl Handle {
+    pub fn new(region: Region, realm: u32, id: u64) -> Self {
+        Handle { region, realm, id }
+    }
+}
+
+impl FromStr for Handle {
+    type Err = HandleError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let malformed = || HandleError::Malformed(text.to_string());
+        let [region, game, realm, id] = text.split('-').collect::<Vec<_>>()[..] else {
+            return Err(malformed());
+        };
+        if game != GAME {
+            return Err(malformed());
+        }
+        let region_code: u32 = number(region).ok_or_else(malformed)?;
+        let region = Region::from_code(region_code).ok_or_else(|| HandleError::UnknownRegion {
+            handle: text.to_string(),
+            region: region.to_string(),
+        })?;
+        Ok(Handle {
+            region,
+            realm: number(realm).ok_or_else(malformed)?,
+            id: number(id).ok_or_else(malformed)?,
+        })
+    }
+}
+
+/// A decimal number in canonical form.
+fn number<T: FromStr>(text: &str) -> Option<T> {
+    let canonical = !text.is_empty()
+        && text.bytes().all(|byte| byte.is_ascii_digit())
+        && (text == "0" || !text.starts_with('0'));
+    if canonical { text.parse().ok() } else { None }
+}
+
+impl fmt::Display for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}-{}", self.region.code(), GAME, self.realm, self.id)
+    }
+}
+
+/// Handles of one region and realm whose ids are in a range, such as
+/// `1-S2-1-1000..2000`, both ends included. A single handle is a range of one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandleRange {
+    pub region: Region,
+    pub realm: u32,
+    pub ids: RangeInclusive<u64>,
+}
+
+impl HandleRange {
+    /// The number of handles in the range, which for every id is one more than `u64` holds.
+    pub fn count(&self) -> u128 {
+        u128::from(self.ids.end() - self.ids.start()) + 1
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.ids.clone().map(|id| Handle::new(self.region, self.realm, id))
+    }
+}
+
+impl From<Handle> for HandleRange {
+    fn from(handle: Handle) -> Self {
+        HandleRange {
+            region: handle.region,
+            realm: handle.realm,
+            ids: handle.id..=handle.id,
+        }
+    }
+}
+
+impl FromStr for HandleRange {
+    type Err = HandleError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let Some((first, last)) = text.split_once("..") else {
+            return text.parse::<Handle>().map(HandleRange::from);
+        };
+        let first: Handle = first.parse()?;
+        let last: u64 = number(last).ok_or_else(|| HandleError::Malformed(text.to_string()))?;
+        if last < first.id {
+            return Err(HandleError::EmptyRange(text.to_string()));
+        }
+        Ok(HandleRange {
+            ids: first.id..=last,
+            ..HandleRange::from(first)
+        })
+    }
+}
+
+impl fmt::Display for HandleRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Handle::new(self.region, self.realm, *self.ids.start()))?;
+        if self.ids.end() != self.ids.start() {
+            write!(f, "..{}", self.ids.end())?;
+        }
+        Ok(())
+    }
+}
+
+/// The handle of the map author whose banks these are: the directory a bank is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AuthorHandle(pub Handle);
+
+/// The handle of the player a bank belongs to: the directory holding `Banks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerHandle(pub Handle);
+
+macro_rules! handle_newtype {
+    ($name:ident) => {
+        impl FromStr for $name {
+            type Err = HandleError;
+
+            fn from_str(text: &str) -> Result<Self, Self::Err> {
+                text.parse().map($name)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl From<Handle> for $name {
+            fn from(handle: Handle) -> Self {
+                $name(handle)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+    };
+}
+
+handle_newtype!(AuthorHandle);
+handle_newtype!(PlayerHandle);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn malformed(text: &str) -> Result<Handle, HandleError> {
+        Err(HandleError::Malformed(text.to_string()))
+    }
+
+    #[test]
+    fn parses_canonical_handles() {
+        let handle: Handle = "2-S2-1-1234567".parse().unwrap();
+        assert_eq!(handle, Handle::new(Region::Eu, 1, 1234567));
+        assert_eq!(handle.to_string(), "2-S2-1-1234567");
+        assert_eq!("1-S2-0-0".parse(), Ok(Handle::new(Region::Us, 0, 0)));
+        let largest = format!("1-S2-{}-{}", u32::MAX, u64::MAX);
+        assert_eq!(largest.parse(), Ok(Handle::new(Region::Us, u32::MAX, u64::MAX)));
+    }
+
+    #[test]
+    fn rejects_handles_that_are_not_canonical() {
+        for text in [
+            "01-S2-1-1234567",
+            "1-S2-01-1234567",
+            "1-S2-1-01234567",
+            "1-S2-1-+1234567",
+            "1-S2-1-",
+            "1-s2-1-1234567",
+            "1-S1-1-1234567",
+            "1-S2-1",
+            "1-S2-1-1-1",
+            " 1-S2-1-1234567",
+            "1-S2-4294967296-1",
+            "1-S2-1-18446744073709551616",
+        ] {
+            assert_eq!(text.parse::<Handle>(), malformed(text));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_regions() {
+        for region in ["0", "4", "6", "99"] {
+            let text = format!("{}-S2-1-1234567", region);
+            assert_eq!(
+                text.parse::<Handle>(),
+                Err(HandleError::UnknownRegion { handle: text.clone(), region: region.to_string() })
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_regions() {
+        let regions = [(1, Region::Us), (2, Region::Eu), (3, Region::Kr), (5, Region::Cn), (98, Region::Ptr)];
+        for (code, region) in regions {
+            assert_eq!(Region::from_code(code), Some(region));
+            assert_eq!(region.code(), code);
+            let handle: Handle = format!("{}-S2-1-1", code).parse().unwrap();
+            assert_eq!(handle.region, region);
+        }
+        assert_eq!(Region::Ptr.to_string(), "PTR");
+        assert_eq!(Region::from_code(4), None);
+    }
+
+    #[test]
+    fn newtypes_parse_and_display_as_handles() {
+        let author: AuthorHandle = "1-S2-1-1234567".parse().unwrap();
+        let player: PlayerHandle = "2-S2-1-7654321".parse().unwrap();
+        assert_eq!(author, AuthorHandle(Handle::new(Region::Us, 1, 1234567)));
+        assert_eq!(player, PlayerHandle::from(Handle::new(Region::Eu, 1, 7654321)));
+        assert_eq!(author.to_string(), "1-S2-1-1234567");
+        assert_eq!(format!("{}", player), "2-S2-1-7654321");
+        assert!("1-S2-1-01".parse::<AuthorHandle>().is_err());
+        assert!("7-S2-1-1".parse::<PlayerHandle>().is_err());
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let range: HandleRange = "1-S2-1-1000..1002".parse().unwrap();
+        assert_eq!(range, HandleRange { region: Region::Us, realm: 1, ids: 1000..=1002 });
+        assert_eq!(range.count(), 3);
+        let handles: Vec<String> = range.handles().map(|handle| handle.to_string()).collect();
+        assert_eq!(handles, ["1-S2-1-1000", "1-S2-1-1001", "1-S2-1-1002"]);
+        assert_eq!(range.to_string(), "1-S2-1-1000..1002");
+
+        let single: HandleRange = "2-S2-1-5".parse().unwrap();
+        assert_eq!(single.ids, 5..=5);
+        assert_eq!(single.count(), 1);
+        assert_eq!(single.to_string(), "2-S2-1-5");
+        assert_eq!("2-S2-1-5..5".parse(), Ok(single));
+    }
+
+    #[test]
+    fn rejects_reversed_and_malformed_ranges() {
+        let reversed = "1-S2-1-2000..1000";
+        assert_eq!(reversed.parse::<HandleRange>(), Err(HandleError::EmptyRange(reversed.to_string())));
+        for text in ["1-S2-1-1000..", "1-S2-1-1000..01000", "1-S2-1-1000..2-S2-1-2000"] {
+            assert_eq!(text.parse::<HandleRange>(), Err(HandleError::Malformed(text.to_string())));
+        }
+        assert!("1-S2-1-01..5".parse::<HandleRange>().is_err());
+    }
+
+    #[test]
+    fn counts_every_id() {
+        let range: HandleRange = format!("1-S2-1-0..{}", u64::MAX).parse().unwrap();
+        assert_eq!(range.count(), u128::from(u64::MAX) + 1);
+    }
+}
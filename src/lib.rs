@@ -1,38 +1,404 @@
 use std::io;
 use std::path::PathBuf;
-use crate::bank_parser::BankParserError;
+use crate::bank_parser::attribute::Attribute;
+use crate::bank_parser::diagnostic::Diagnostic;
+use crate::bank_parser::source_map::SourceMap;
+use crate::bank_parser::key_path::KeyPath;
+use crate::bank_parser::{BankParserError, ParseMode};
+use crate::backup::{BackupPolicy, DEFAULT_KEEP};
 use crate::bank_path::BankPathError;
-use clap::Parser;
-use regex::Error as RegexError;
+use crate::handle::{AuthorHandle, HandleError, HandleRange, PlayerHandle};
+use crate::recover::RecoverError;
+use clap::{Parser, Subcommand, ValueEnum};
+use xml::common::Position;
 
+pub mod backup;
+pub mod bank_file;
 pub mod bank_parser;
 pub mod bank_path;
+pub mod bank_store;
+pub mod batch;
+pub mod handle;
+pub mod recover;
+pub mod signing_context;
+#[cfg(target_os = "linux")]
+pub mod watch;
 
 /// A simple CLI tool to validate and resign StarCraft II bank files.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Bank to verify when no subcommand is given. A directory or glob pattern
+    /// verifies every bank it contains
+    #[command(flatten)]
+    pub bank: Option<BankArgs>,
+
+    /// Further banks, directories or glob patterns to verify along with BANK_PATH
+    #[arg(value_name = "MORE_PATHS")]
+    pub more_paths: Vec<String>,
+
+    /// Write the computed signature back to the file if it differs
+    #[arg(short = 'w', long = "write", action)]
+    pub write: bool,
+
+    /// How to print the verification results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+
+/// How banks are backed up before the tool overwrites them.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BackupArgs {
+    /// Directory to keep backups in [default: a .backups directory next to each bank]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Number of backups to keep per bank
+    #[arg(long, global = true, value_name = "N", default_value_t = DEFAULT_KEEP)]
+    pub keep_backups: usize,
+
+    /// Overwrite banks without backing them up
+    #[arg(long, global = true, action)]
+    pub no_backup: bool,
+}
+
+impl BackupArgs {
+    pub fn policy(&self) -> BackupPolicy {
+        if self.no_backup {
+            return BackupPolicy::disabled();
+        }
+        BackupPolicy { dir: self.backup_dir.clone(), keep: self.keep_backups }
+    }
+}
+
+/// How banks are read.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ParseArgs {
+    /// Read unparsable values as defaults instead of rejecting a bank
+    #[arg(long, action)]
+    pub lenient: bool,
+}
+
+impl ParseArgs {
+    pub fn mode(&self) -> ParseMode {
+        if self.lenient {
+            ParseMode::Lenient
+        } else {
+            ParseMode::Strict
+        }
+    }
+}
+
+/// The bank file to work on, and overrides for the metadata derived from its path.
+///
+/// clap leaves the group of a struct with a flattened field empty, and an empty group
+/// would make `Args::bank` always `None`, so the arguments join it themselves.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BankArgs {
     /// Filepath to the bank file (.SC2Bank)
-    #[arg(value_name = "BANK_PATH")]
+    #[arg(value_name = "BANK_PATH", group = "BankArgs")]
     pub bank_path: String,
 
     /// Override bank name (if different from file name without extension)
-    #[arg(short = 'n', long)]
+    #[arg(short = 'n', long, group = "BankArgs")]
     pub bank_name: Option<String>,
 
-    /// Override author handle (e.g., 1-S2-1-AUTHID)
-    #[arg(short = 'a', long = "author")]
-    pub author_handle: Option<String>,
+    /// Override author handle (e.g., 1-S2-1-1234567)
+    #[arg(short = 'a', long = "author", group = "BankArgs")]
+    pub author_handle: Option<AuthorHandle>,
 
-    /// Override player handle (e.g., 2-S2-1-PLAYER-HANDLE)
-    #[arg(short = 'p', long = "player",)]
-    pub player_handle: Option<String>,
+    /// Override player handle (e.g., 2-S2-1-7654321)
+    #[arg(short = 'p', long = "player", group = "BankArgs")]
+    pub player_handle: Option<PlayerHandle>,
 
-    /// Write the computed signature back to the file if it differs
-    #[arg(short = 'w', long = "write", action)]
-    pub write: bool,
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupsCommand {
+    /// List the backups of a bank, oldest first
+    List {
+        /// Bank whose backups to list
+        #[arg(value_name = "BANK_PATH")]
+        bank: PathBuf,
+    },
+}
+
+/// Options shared by the commands that edit a bank.
+#[derive(clap::Args, Debug, Clone)]
+pub struct EditArgs {
+    /// Print the resulting diff and signature instead of writing the file
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// How `explain` prints the signing payload.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// One item per line with its kind; excluded text values are marked
+    List,
+    /// The exact bytes that are hashed
+    Raw,
+    /// The hashed bytes as hex
+    Hex,
+}
+
+/// How verification results are printed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Bank details and log lines, or a table for several banks
+    Text,
+    /// A JSON object per bank, one per line
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// Interchange formats `export` can write.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Show what changed between two banks, ignoring layout and ordering
+    Diff {
+        /// Old version of the bank
+        old: PathBuf,
+        /// New version of the bank
+        new: PathBuf,
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Merge two versions of a bank that share a base version, key by key
+    Merge {
+        /// Common ancestor of both versions
+        base: PathBuf,
+        /// Our version
+        ours: PathBuf,
+        /// Their version
+        theirs: PathBuf,
+        /// Where to write the merged bank, which is signed for this path
+        #[command(flatten)]
+        bank: BankArgs,
+    },
+    /// Git merge driver: merge into OURS, signed for the bank at BANK_PATH (%P)
+    ///
+    /// Configure with `driver = sc2_bank_signer merge-driver %O %A %B %P`, adding `-a` and
+    /// `-p` unless the repository mirrors the Accounts layout, as %P is relative to the
+    /// repository. Exits with an error when keys conflict, leaving our values for them in place.
+    MergeDriver {
+        /// Common ancestor of both versions (%O)
+        base: PathBuf,
+        /// Our version (%A), replaced by the merged bank
+        ours: PathBuf,
+        /// Their version (%B)
+        theirs: PathBuf,
+        #[command(flatten)]
+        bank: BankArgs,
+    },
+    /// Watch a directory and re-sign banks whenever they are changed outside the game
+    #[cfg(target_os = "linux")]
+    Watch {
+        /// Directory to watch, e.g. an Accounts or Banks folder
+        dir: PathBuf,
+        /// Milliseconds without further changes to wait before re-signing a bank
+        #[arg(long, default_value_t = 500)]
+        debounce: u64,
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// List the accounts, players, authors and banks of an Accounts directory with
+    /// the signature status of each bank
+    Inventory {
+        /// The Accounts directory, or the StarCraft II directory containing it
+        root: PathBuf,
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Copy a bank to another player or author and re-sign it for its new place
+    Transfer {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Player handle to copy the bank to
+        #[arg(long, value_name = "HANDLE", required_unless_present = "all_players")]
+        to_player: Option<PlayerHandle>,
+        /// Author handle to file the bank under, by default the bank's own
+        #[arg(long, value_name = "HANDLE")]
+        to_author: Option<AuthorHandle>,
+        /// Account directory to copy the bank into, by default the bank's own
+        #[arg(long, value_name = "DIR")]
+        to_account_root: Option<PathBuf>,
+        /// Copy the bank to every player of the account
+        #[arg(long, action, conflicts_with_all = ["to_player", "move_bank"])]
+        all_players: bool,
+        /// Remove the original bank once it has been copied
+        #[arg(long = "move", action)]
+        move_bank: bool,
+        /// Overwrite banks that already exist at the destination
+        #[arg(long, action)]
+        force: bool,
+    },
+    /// List or inspect the backups of a bank
+    Backups {
+        #[command(subcommand)]
+        command: BackupsCommand,
+    },
+    /// Find the author handle, player handle and bank name a bank was signed with
+    Recover {
+        /// Bank whose signing context to find
+        #[arg(value_name = "BANK_PATH")]
+        bank: PathBuf,
+        /// Candidate author handle, or a range of ids such as 1-S2-1-1000..2000 (both
+        /// ends included); repeat for more candidates
+        #[arg(short = 'a', long = "author", value_name = "HANDLE", required = true)]
+        authors: Vec<HandleRange>,
+        /// Candidate player handle or range of ids, as for --author
+        #[arg(short = 'p', long = "player", value_name = "HANDLE", required = true)]
+        players: Vec<HandleRange>,
+        /// Candidate bank name; repeat for more candidates. Defaults to the file name
+        /// without extension
+        #[arg(short = 'n', long = "bank-name", value_name = "BANK_NAME")]
+        bank_names: Vec<String>,
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Put a backup of a bank back in its place, by default the latest one
+    Restore {
+        /// Bank to restore
+        #[arg(value_name = "BANK_PATH")]
+        bank: PathBuf,
+        /// Restore the latest backup taken at or before this time, e.g. 20261017T1203 or
+        /// 2026-10-17T12:03 (UTC)
+        #[arg(long, value_name = "TIMESTAMP")]
+        at: Option<String>,
+    },
+    /// Print a bank as one line per key, for `git diff` textconv
+    Textconv {
+        /// Bank file to print
+        path: PathBuf,
+    },
+    /// Print the signing payload of a bank, to see why a signature differs
+    Explain {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Output format of the payload
+        #[arg(long, value_enum, default_value_t = PayloadFormat::List)]
+        format: PayloadFormat,
+    },
+    /// Export the sections, keys and typed values of a bank
+    #[cfg(feature = "serde")]
+    Export {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Interchange format to write
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// File to write to instead of standard output
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+    },
+    /// Create a signed bank from an exported JSON document
+    #[cfg(feature = "serde")]
+    Import {
+        /// JSON document written by `export`
+        #[arg(value_name = "JSON_PATH")]
+        json_path: PathBuf,
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Overwrite the bank if it already exists
+        #[arg(long, action)]
+        force: bool,
+    },
+    /// Apply a patch file to one or more banks and re-sign them
+    ///
+    /// The patch is applied to every bank before any is written, so if it does not apply
+    /// to one of them, none is changed.
+    #[cfg(feature = "serde")]
+    ApplyPatch {
+        /// Patch file, TOML if it ends in .toml and JSON otherwise
+        #[arg(value_name = "PATCH")]
+        patch: PathBuf,
+        /// Banks to patch
+        #[arg(value_name = "BANK_PATH", required = true)]
+        banks: Vec<String>,
+        /// Override author handle of every bank
+        #[arg(short = 'a', long = "author")]
+        author_handle: Option<AuthorHandle>,
+        /// Override player handle of every bank
+        #[arg(short = 'p', long = "player")]
+        player_handle: Option<PlayerHandle>,
+        #[command(flatten)]
+        parse: ParseArgs,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// Check banks against a schema of their sections, keys and values
+    #[cfg(feature = "serde")]
+    Validate {
+        /// Schema file, TOML (.bankschema) or JSON if it ends in .json
+        #[arg(long, value_name = "SCHEMA")]
+        schema: PathBuf,
+        /// Banks to check
+        #[arg(value_name = "BANK_PATH", required = true)]
+        banks: Vec<PathBuf>,
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Set the value of a key and re-sign the bank
+    Set {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Key to set, as Section/Key (created if missing)
+        #[arg(value_name = "SECTION/KEY")]
+        key: KeyPath,
+        /// Typed value, e.g. int=42, fixed=1.5, flag=1, string=abc or text=abc
+        #[arg(value_name = "TYPE=VALUE")]
+        value: Attribute,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// Delete a key and re-sign the bank
+    Delete {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Key to delete, as Section/Key
+        #[arg(value_name = "SECTION/KEY")]
+        key: KeyPath,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// Rename a key within its section and re-sign the bank
+    RenameKey {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Key to rename, as Section/Key
+        #[arg(value_name = "SECTION/KEY")]
+        key: KeyPath,
+        /// New key name
+        new_name: String,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// Rename a section and re-sign the bank
+    RenameSection {
+        #[command(flatten)]
+        bank: BankArgs,
+        /// Section to rename
+        section: String,
+        /// New section name
+        new_name: String,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+}
 
 pub type AppResult<T> = Result<T, AppError>;
 
@@ -48,15 +414,68 @@ pub enum AppError {
     #[error("Bank Path Error: {0}")]
     BankPathError(#[from] BankPathError),
 
+    #[error("Handle Error: {0}")]
+    HandleError(#[from] HandleError),
+
+    #[error("Recover Error: {0}")]
+    RecoverError(#[from] RecoverError),
+
     #[error("Bank Content Error: {0}")]
     BankParseError(#[from] BankParserError),
 
-    #[error("Signature tag not found or missing in bank file")]
-    SignatureNotFound,
+    #[cfg(feature = "serde")]
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
 
-    #[error("Regex Error")]
-    RegexError(#[from] RegexError),
+    #[cfg(feature = "serde")]
+    #[error("TOML Error: {0}")]
+    TomlError(#[from] toml::de::Error),
+
+    #[error("File already exists: {0}")]
+    FileExists(PathBuf),
+
+    #[error("{0} changed on disk after it was read, not overwriting it; run again to work on its current content")]
+    ChangedOnDisk(PathBuf),
+
+    #[error("{0} is given more than once")]
+    DuplicateBank(PathBuf),
+
+    #[error("No backup of {0} to restore")]
+    NoBackup(PathBuf),
+
+    #[error("Could not derive the signing metadata from the bank path '{0}' (git's %P): {1}; \
+             give it with -n, -a and -p in the merge driver command")]
+    MergeDriverPath(String, BankPathError),
+
+    #[error("Merge left {0} conflicting key(s)")]
+    MergeConflicts(usize),
+
+    #[error("Found {0} problem(s) with the bank(s) against the schema")]
+    SchemaViolations(usize),
+
+    #[error("Invalid glob pattern: {0}")]
+    GlobPatternError(#[from] glob::PatternError),
+
+    #[error("Glob Error: {0}")]
+    GlobError(#[from] glob::GlobError),
 
     #[error("File not found: {0}")]
-    FileNotFound(PathBuf)
+    FileNotFound(PathBuf),
+
+    #[error("Bank was not loaded from a file, there is nothing to write back to")]
+    NoSourcePath,
+}
+
+impl AppError {
+    /// The error as diagnostics located in `source`, the text of the bank that failed.
+    pub fn diagnostics(&self, source: &str) -> Vec<Diagnostic> {
+        match self {
+            AppError::XmlReaderError(err) => {
+                let span = SourceMap::new(source).span_at(source, err.position());
+                vec![Diagnostic::error(err.msg(), Some(span))]
+            }
+            AppError::BankParseError(err) => err.diagnostics(),
+            _ => vec![Diagnostic::error(self.to_string(), None)],
+        }
+    }
 }
@@ -1,8 +1,42 @@
 use clap::Parser;
-use log::{error, info, LevelFilter};
-use sc2_bank_signer::bank_parser::BankParser;
-use sc2_bank_signer::{AppResult, Args};
+use log::{error, info, warn, LevelFilter};
+use sc2_bank_signer::backup::BackupPolicy;
+use sc2_bank_signer::bank_parser::diff::{BankDiff, Listing};
+use sc2_bank_signer::bank_parser::merge::BankMerge;
+use sc2_bank_signer::bank_parser::{BankParser, ParseMode};
+use sc2_bank_signer::bank_path::BankPath;
+use sc2_bank_signer::bank_store::{self, BankStore};
+use rayon::prelude::*;
+use sc2_bank_signer::bank_parser::signature::VerificationStatus;
+use sc2_bank_signer::batch::{self, BatchEntry, BatchStatus};
+use sc2_bank_signer::handle::{AuthorHandle, PlayerHandle};
+use sc2_bank_signer::recover::{self, Candidates};
+use sc2_bank_signer::signing_context::SigningContext;
+use sc2_bank_signer::{AppError, AppResult, Args, BackupsCommand, BankArgs, Command, EditArgs, OutputFormat, PayloadFormat};
+#[cfg(feature = "serde")]
+use sc2_bank_signer::ExportFormat;
+#[cfg(feature = "serde")]
+use sc2_bank_signer::bank_parser::patch::Patch;
+#[cfg(feature = "serde")]
+use sc2_bank_signer::bank_parser::schema::Schema;
+#[cfg(target_os = "linux")]
+use sc2_bank_signer::watch::BankWatcher;
+use similar::TextDiff;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
 
+/// Exit codes, for scripts to branch on the outcome of a verification. Other commands
+/// exit with `EXIT_SUCCESS` or `EXIT_ERROR`. 2 is left out: clap exits with it on
+/// command line errors.
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_MISMATCH: i32 = 1;
+const EXIT_ERROR: i32 = 3;
+const EXIT_MISSING_SIGNATURE: i32 = 4;
 
 fn setup_logger() {
 
@@ -12,42 +46,643 @@ fn setup_logger() {
 }
 
 
-fn run_app() -> AppResult<()>{
-    let args = Args::parse();
-    setup_logger();
+/// Loads the bank, printing any located problems with the offending source lines.
+fn load_bank(bank: &BankArgs) -> AppResult<(BankPath, BankParser)> {
+    let bank_path = BankPath::new(bank)?;
+    let bank_parser = parse_bank(&bank_path.full_path, bank_path.context(), bank.parse.mode())?;
+    Ok((bank_path, bank_parser))
+}
+
+/// Parses the bank at `path`, rendering its diagnostics to stderr.
+fn parse_bank(path: &Path, context: SigningContext, mode: ParseMode) -> AppResult<BankParser> {
+    let file_name = path.display().to_string();
+    match BankParser::from_path_with_mode(path, context, mode) {
+        Ok(bank_parser) => {
+            let source = bank_parser.to_xml();
+            for diagnostic in &bank_parser.diagnostics {
+                eprint!("{}", diagnostic.render(&source, &file_name));
+            }
+            Ok(bank_parser)
+        }
+        Err(err) => {
+            if let Ok(source) = fs::read_to_string(path) {
+                for diagnostic in err.diagnostics(&source).iter().filter(|d| d.span.is_some()) {
+                    eprint!("{}", diagnostic.render(&source, &file_name));
+                }
+            }
+            Err(err)
+        }
+    }
+}
+
+/// How bad a verification outcome is, for picking the worst of a batch: an error,
+/// then a missing signature, then a mismatch.
+fn severity(status: &BatchStatus) -> u8 {
+    match status {
+        BatchStatus::Match | BatchStatus::Resigned => 0,
+        BatchStatus::Mismatch => 1,
+        BatchStatus::MissingSignature => 2,
+        BatchStatus::Error(_) => 3,
+    }
+}
+
+/// The exit code for a verification outcome. Re-signed banks count as matching, as
+/// they do once written.
+fn exit_code(status: &BatchStatus) -> i32 {
+    match status {
+        BatchStatus::Match | BatchStatus::Resigned => EXIT_SUCCESS,
+        BatchStatus::Mismatch => EXIT_MISMATCH,
+        BatchStatus::MissingSignature => EXIT_MISSING_SIGNATURE,
+        BatchStatus::Error(_) => EXIT_ERROR,
+    }
+}
+
+/// Prints an entry as one line of JSON.
+#[cfg(feature = "serde")]
+fn print_json(entry: &BatchEntry) -> AppResult<()> {
+    println!("{}", serde_json::to_string(entry)?);
+    Ok(())
+}
+
+fn verify(bank: &BankArgs, write: bool, output: OutputFormat, backup: &BackupPolicy) -> AppResult<i32> {
+    match output {
+        OutputFormat::Text => {}
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => {
+            let entry = batch::verify_path(Path::new(&bank.bank_path), bank, write, backup);
+            print_json(&entry)?;
+            return Ok(exit_code(&entry.status));
+        }
+    }
+
     // Create the parser (which also computes the signature)
-    let bank_parser = BankParser::new(&args)?;
+    let (bank_path, mut bank_parser) = load_bank(bank)?;
+    bank_parser.backup = backup.clone();
 
-    println!("{}", bank_parser.bank_path);
+    println!("{}", bank_path);
 
-    let matches = bank_parser.compare_signature();
+    let report = bank_parser.verify();
+    match report.status {
+        VerificationStatus::Match => info!("Signature MATCHES: {}", report.computed_signature),
+        VerificationStatus::Mismatch => {
+            warn!("Signature MISMATCH:");
+            warn!("  File:     {}", report.file_signature.as_deref().unwrap_or_default());
+            warn!("  Computed: {}", report.computed_signature);
+        }
+        VerificationStatus::MissingSignature => {
+            info!("No existing signature found in the XML file.");
+            info!("Computed signature: {}", report.computed_signature);
+        }
+    }
+    let matches = report.status == VerificationStatus::Match;
 
     // Handle writing back to file
-    if args.write {
+    if write {
         if matches {
             info!("Signature already matches. No replacement needed.");
         } else {
             info!("Signature differs, attempting replacement...");
             bank_parser.replace_signature()?;
             info!("Bank file signature updated successfully.");
+            return Ok(EXIT_SUCCESS);
         }
     } else if !matches {
         info!("Signature does not match. Run with --write (-w) flag to replace the signature in the file.");
     } else {
         info!("Signature matches. No action requested.");
     }
+    Ok(match report.status {
+        VerificationStatus::Match => EXIT_SUCCESS,
+        VerificationStatus::Mismatch => EXIT_MISMATCH,
+        VerificationStatus::MissingSignature => EXIT_MISSING_SIGNATURE,
+    })
+}
+
+/// Verifies every bank found under the given paths and prints a summary table, or a
+/// line of JSON per bank. The exit code is that of the worst outcome.
+fn verify_all(bank: &BankArgs, more_paths: &[String], write: bool, output: OutputFormat, backup: &BackupPolicy) -> AppResult<i32> {
+    let inputs: Vec<String> = std::iter::once(bank.bank_path.clone()).chain(more_paths.iter().cloned()).collect();
+    let banks = batch::find_banks(&inputs)?;
+    info!("Verifying {} bank(s)...", banks.len());
+    let entries = batch::verify_batch(&banks, bank, write, backup);
+    let code = entries
+        .iter()
+        .map(|entry| &entry.status)
+        .max_by_key(|status| severity(status))
+        .map_or(EXIT_SUCCESS, exit_code);
+
+    match output {
+        OutputFormat::Text => print_table(&entries),
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => {
+            for entry in &entries {
+                print_json(entry)?;
+            }
+        }
+    }
+    Ok(code)
+}
+
+fn print_table(entries: &[BatchEntry]) {
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|entry| {
+            let (author, player) = match &entry.bank_path {
+                Some(bank_path) => (bank_path.author_handle.to_string(), bank_path.player_handle.to_string()),
+                None => ("?".to_string(), "?".to_string()),
+            };
+            [entry.path.display().to_string(), author, player, entry.status.to_string()]
+        })
+        .collect();
+    let header = ["Bank", "Author", "Player", "Status"].map(String::from);
+    let mut widths = header.clone().map(|title| title.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0], row[1], row[2], row[3],
+            w0 = widths[0], w1 = widths[1], w2 = widths[2]
+        );
+    }
+
+    let count = |wanted: fn(&BatchStatus) -> bool| entries.iter().filter(|entry| wanted(&entry.status)).count();
+    println!(
+        "{} bank(s): {} match, {} mismatch, {} missing signature, {} re-signed, {} error",
+        entries.len(),
+        count(|status| *status == BatchStatus::Match),
+        count(|status| *status == BatchStatus::Mismatch),
+        count(|status| *status == BatchStatus::MissingSignature),
+        count(|status| *status == BatchStatus::Resigned),
+        count(|status| matches!(status, BatchStatus::Error(_))),
+    );
+    for entry in entries {
+        if let BatchStatus::Error(message) = &entry.status {
+            eprintln!("error: {}: {}", entry.path.display(), message);
+        }
+    }
+}
+
+/// Prints the banks of an Accounts directory as a tree, each with its signature status.
+fn inventory(root: &Path, mode: ParseMode) -> AppResult<()> {
+    let store = BankStore::open(root)?;
+    let banks: Vec<&BankPath> = store.banks().collect();
+    let entries: Vec<BatchEntry> = banks.par_iter().map(|bank| batch::check_bank(bank, mode, false, &BackupPolicy::disabled())).collect();
+    let mut statuses = entries.iter().map(|entry| &entry.status);
+
+    println!("{}", store.root.display());
+    for account in &store.accounts {
+        println!("  Account {}", account.id);
+        for player in &account.players {
+            println!("    Player {}", player.handle);
+            for author in &player.authors {
+                println!("      Author {}", author.handle);
+                let width = author.banks.iter().map(|bank| bank.bank_name.chars().count()).max().unwrap_or(0);
+                for bank in &author.banks {
+                    let status = statuses.next().expect("a status per bank");
+                    println!("        {:<width$}  {}", bank.bank_name, status);
+                    if let BatchStatus::Error(message) = status {
+                        eprintln!("error: {}: {}", bank.full_path.display(), message);
+                    }
+                }
+            }
+        }
+    }
+    println!("{} bank(s) in {} account(s)", banks.len(), store.accounts.len());
+    Ok(())
+}
+
+/// Searches the candidate contexts for the one the bank was signed with, and prints
+/// every match.
+fn recover_context(bank: &Path, mut candidates: Candidates, mode: ParseMode) -> AppResult<()> {
+    let file_stem = bank.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    if candidates.bank_names.is_empty() {
+        candidates.bank_names.push(file_stem.clone());
+    }
+    let bank_parser = parse_bank(bank, SigningContext::unsigned(file_stem), mode)?;
+
+    info!("Trying {} signing context(s)...", candidates.count());
+    let found = recover::recover(&bank_parser, &candidates)?;
+    if found.len() > 1 {
+        warn!("{} signing contexts produce the bank's signature", found.len());
+    }
+    for context in &found {
+        println!(
+            "Bank Name: '{}'  Author Handle: {}  Player Handle: {}",
+            context.bank_name, context.author_handle, context.player_handle
+        );
+    }
+    Ok(())
+}
+
+/// Prints the signing payload of the bank in the requested format.
+fn explain(bank: &BankArgs, format: PayloadFormat) -> AppResult<()> {
+    let (_, bank_parser) = load_bank(bank)?;
+    let payload = bank_parser.payload();
+
+    match format {
+        PayloadFormat::List => {
+            for (index, item) in payload.items.iter().enumerate() {
+                let marker = if item.is_signed() { "" } else { " (not signed)" };
+                println!("{:>5}  {:<14} {}{}", index, item.kind, item.text.escape_debug(), marker);
+            }
+            println!("Signature: {}", payload.signature);
+        }
+        PayloadFormat::Raw => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&payload.bytes())?;
+            stdout.flush()?;
+        }
+        PayloadFormat::Hex => println!("{}", hex::encode(payload.bytes())),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn export(bank: &BankArgs, format: ExportFormat, output: Option<&Path>) -> AppResult<()> {
+    let (_, bank_parser) = load_bank(bank)?;
+    let exported = match format {
+        ExportFormat::Json => bank_parser.to_json()?,
+    };
+    match output {
+        Some(path) => {
+            fs::write(path, exported + "\n")?;
+            info!("Bank exported to {}", path.display());
+        }
+        None => println!("{}", exported),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn import(json_path: &Path, bank: &BankArgs, force: bool, backup: &BackupPolicy) -> AppResult<()> {
+    let bank_path = BankPath::for_new_bank(bank)?;
+    if bank_path.full_path.exists() && !force {
+        return Err(AppError::FileExists(bank_path.full_path));
+    }
+    let json = fs::read_to_string(json_path)?;
+    let mut bank_parser = BankParser::from_json(&json, bank_path.context())?;
+    bank_parser.source_path = Some(bank_path.full_path.clone());
+    bank_parser.backup = backup.clone();
+    bank_parser.save()?;
+    info!("Bank written to {}, signature: {}", bank_path.full_path.display(), bank_parser.signature);
     Ok(())
 }
 
+/// Where to copy a bank with `transfer`.
+struct TransferTarget<'a> {
+    /// `None` for every player of the account.
+    player: Option<PlayerHandle>,
+    author: Option<AuthorHandle>,
+    account_root: Option<&'a Path>,
+}
+
+/// Copies the bank to the players of `target`, re-signed for each, and removes the
+/// original with `move_bank`.
+fn transfer(bank: &BankArgs, target: TransferTarget, move_bank: bool, force: bool, backup: &BackupPolicy) -> AppResult<()> {
+    let bank_path = BankPath::new(bank)?;
+    let account = match target.account_root {
+        Some(root) => root.to_path_buf(),
+        None => bank_path.account_dir()?.to_path_buf(),
+    };
+    let author = target.author.unwrap_or(bank_path.author_handle);
+    let players = match target.player {
+        Some(player) => vec![player],
+        None => bank_store::player_handles(&account)?,
+    };
+
+    // Check every destination first so that nothing is written if one is taken.
+    let mut destinations = Vec::new();
+    for player in players {
+        let destination = bank_store::bank_location(&account, player, author, &bank_path.bank_name);
+        if destination.canonicalize().ok().as_ref() == Some(&bank_path.full_path) {
+            info!("Skipping {}, it is the bank itself", destination.display());
+            continue;
+        }
+        if destination.exists() && !force {
+            return Err(AppError::FileExists(destination));
+        }
+        destinations.push((player, destination));
+    }
+
+    let mut copies = Vec::with_capacity(destinations.len());
+    for (player, destination) in &destinations {
+        let context = SigningContext::new(&bank_path.bank_name, author, *player);
+        let mut bank_parser = BankParser::from_path_with_mode(&bank_path.full_path, context, bank.parse.mode())?;
+        bank_parser.apply_signature()?;
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        bank_parser.source_path = Some(destination.clone());
+        // Whatever is at the destination is replaced, not the bank that was read.
+        bank_parser.source_hash = None;
+        bank_parser.backup = backup.clone();
+        copies.push(bank_parser);
+    }
+    // All copies or none, so a failed write does not leave some players with the bank.
+    BankParser::save_all(&copies.iter().collect::<Vec<_>>())?;
+    for (copy, (_, destination)) in copies.iter().zip(&destinations) {
+        info!("Bank copied to {}, signature: {}", destination.display(), copy.signature);
+    }
+    if move_bank && !destinations.is_empty() {
+        backup.backup(&bank_path.full_path)?;
+        fs::remove_file(&bank_path.full_path)?;
+        info!("Removed {}", bank_path.full_path.display());
+    }
+    Ok(())
+}
+
+/// Prints the backups of the bank, oldest first.
+fn list_backups(bank: &Path, backup: &BackupPolicy) -> AppResult<()> {
+    let backups = backup.list(bank)?;
+    if backups.is_empty() {
+        println!("No backups of {} in {}", bank.display(), backup.backup_dir(bank).display());
+        return Ok(());
+    }
+    for entry in &backups {
+        let size = fs::metadata(&entry.path)?.len();
+        println!("{}  {:>8} bytes  {}", entry.timestamp, size, entry.path.display());
+    }
+    Ok(())
+}
+
+/// Prints what changed from the `old` bank to the `new` one.
+fn diff(old: &Path, new: &Path, mode: ParseMode) -> AppResult<()> {
+    // Signing metadata does not matter for comparing contents.
+    let context = |path: &Path| SigningContext::unsigned(path.display().to_string());
+    let old_bank = parse_bank(old, context(old), mode)?;
+    let new_bank = parse_bank(new, context(new), mode)?;
+
+    let bank_diff = BankDiff::between(&old_bank, &new_bank);
+    if bank_diff.is_empty() {
+        println!("No differences.");
+    } else {
+        print!("{}", bank_diff);
+    }
+    Ok(())
+}
+
+/// Merges `ours` and `theirs` against `base` and writes the signed result to `output`,
+/// signed for the bank described by `bank`. Conflicts keep our values and are reported.
+/// Merges into a copy of our version of the bank, so its comments and layout are kept,
+/// signs it for `context` and writes it to `output`.
+fn merge(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    context: SigningContext,
+    mode: ParseMode,
+    output: &Path,
+    backup: &BackupPolicy,
+) -> AppResult<()> {
+    // Git passes an empty base when both sides added the bank.
+    let base = if fs::metadata(base)?.len() == 0 {
+        Vec::new()
+    } else {
+        parse_bank(base, context.clone(), mode)?.sections
+    };
+    let mut merged = parse_bank(ours, context.clone(), mode)?;
+    let theirs = parse_bank(theirs, context, mode)?;
+
+    let bank_merge = BankMerge::new(&base, &merged.sections, &theirs.sections);
+    merged.apply_merge(&bank_merge)?;
+    merged.apply_signature()?;
+    if merged.source_path.as_deref() != Some(output) {
+        merged.source_path = Some(output.to_path_buf());
+        merged.source_hash = None;
+    }
+    merged.backup = backup.clone();
+    merged.save()?;
+
+    if bank_merge.is_clean() {
+        info!("Merged bank written to {}, signature: {}", output.display(), merged.signature);
+        return Ok(());
+    }
+    for conflict in &bank_merge.conflicts {
+        eprintln!("conflict: {}", conflict);
+    }
+    Err(AppError::MergeConflicts(bank_merge.conflicts.len()))
+}
+
+/// Prints the bank as one line per key. Reads leniently, so `git diff` still works on
+/// banks with damaged values.
+fn textconv(path: &Path) -> AppResult<()> {
+    let context = SigningContext::unsigned(path.display().to_string());
+    let bank_parser = parse_bank(path, context, ParseMode::Lenient)?;
+    print!("{}", Listing(&bank_parser.sections));
+    Ok(())
+}
+
+/// Applies `edit` to the bank, re-signs it, and either writes it back or prints the diff.
+fn edit_bank(
+    bank: &BankArgs,
+    edit_args: &EditArgs,
+    backup: &BackupPolicy,
+    edit: impl FnOnce(&mut BankParser) -> AppResult<()>,
+) -> AppResult<()> {
+    let (bank_path, mut bank_parser) = load_bank(bank)?;
+    bank_parser.backup = backup.clone();
+    let original = bank_parser.to_xml();
+
+    edit(&mut bank_parser)?;
+    bank_parser.apply_signature()?;
+    let edited = bank_parser.to_xml();
+
+    if edit_args.dry_run {
+        print_edit(&bank_path, &original, &edited, &bank_parser.signature);
+    } else {
+        bank_parser.save()?;
+        info!("Bank file updated, new signature: {}", bank_parser.signature);
+    }
+    Ok(())
+}
+
+/// Prints the diff of an edit and the new signature, for `--dry-run`.
+fn print_edit(bank_path: &BankPath, original: &str, edited: &str, signature: &str) {
+    let file_name = bank_path.full_path.display().to_string();
+    print!(
+        "{}",
+        TextDiff::from_lines(original, edited)
+            .unified_diff()
+            .header(&file_name, &file_name)
+    );
+    println!("New signature: {}", signature);
+}
+
+/// Applies the patch to every bank, writing none of them unless it applies to all.
+#[cfg(feature = "serde")]
+fn apply_patch(patch_path: &Path, banks: &[BankArgs], edit_args: &EditArgs, backup: &BackupPolicy) -> AppResult<()> {
+    let patch = Patch::from_path(patch_path)?;
+
+    let mut patched = Vec::with_capacity(banks.len());
+    for bank in banks {
+        let (bank_path, mut bank_parser) = load_bank(bank)?;
+        bank_parser.backup = backup.clone();
+        let original = bank_parser.to_xml();
+        if let Err(err) = bank_parser.apply_patch(&patch) {
+            error!("Patch does not apply to {}, no bank was changed", bank_path.full_path.display());
+            return Err(err);
+        }
+        patched.push((bank_path, original, bank_parser));
+    }
+
+    if edit_args.dry_run {
+        for (bank_path, original, bank_parser) in &patched {
+            print_edit(bank_path, original, &bank_parser.to_xml(), &bank_parser.signature);
+        }
+        return Ok(());
+    }
+    let banks: Vec<&BankParser> = patched.iter().map(|(_, _, bank_parser)| bank_parser).collect();
+    if let Err(err) = BankParser::save_all(&banks) {
+        error!("Could not write the patched banks, the banks written were put back");
+        return Err(err);
+    }
+    for (bank_path, _, bank_parser) in &patched {
+        info!("Patched {}, new signature: {}", bank_path.full_path.display(), bank_parser.signature);
+    }
+    Ok(())
+}
+
+/// Checks every bank against the schema, printing each problem with its location.
+#[cfg(feature = "serde")]
+fn validate(schema_path: &Path, banks: &[PathBuf], mode: ParseMode) -> AppResult<()> {
+    let schema = Schema::from_path(schema_path)?;
+    let mut problems = 0;
+    for bank in banks {
+        let bank_name = bank.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let bank_parser = parse_bank(bank, SigningContext::unsigned(bank_name), mode)?;
+        let diagnostics = schema.validate(&bank_parser);
+        if diagnostics.is_empty() {
+            info!("{} matches the schema", bank.display());
+            continue;
+        }
+        let source = bank_parser.to_xml();
+        let file_name = bank.display().to_string();
+        for diagnostic in &diagnostics {
+            eprint!("{}", diagnostic.render(&source, &file_name));
+        }
+        problems += diagnostics.len();
+    }
+    if problems > 0 {
+        return Err(AppError::SchemaViolations(problems));
+    }
+    Ok(())
+}
+
+fn run_app() -> AppResult<i32>{
+    let args = Args::parse();
+    setup_logger();
+
+    let backup = args.backup.policy();
+    let Some(command) = &args.command else {
+        let bank = args.bank.as_ref().expect("clap requires BANK_PATH without a subcommand");
+        return if args.more_paths.is_empty() && Path::new(&bank.bank_path).is_file() {
+            verify(bank, args.write, args.output, &backup)
+        } else {
+            verify_all(bank, &args.more_paths, args.write, args.output, &backup)
+        };
+    };
+    match command {
+        Command::Diff { old, new, parse } => diff(old, new, parse.mode()),
+        Command::Merge { base, ours, theirs, bank } => {
+            let context = BankPath::for_new_bank(bank)?.context();
+            merge(base, ours, theirs, context, bank.parse.mode(), Path::new(&bank.bank_path), &backup)
+        }
+        // Git keeps the versions being merged, and `ours` is a temporary file.
+        Command::MergeDriver { base, ours, theirs, bank } => {
+            // %P is relative to the repository, which need not mirror the Accounts layout.
+            let context = match BankPath::for_new_bank(bank) {
+                Ok(bank_path) => bank_path.context(),
+                Err(AppError::BankPathError(err)) => {
+                    return Err(AppError::MergeDriverPath(bank.bank_path.clone(), err));
+                }
+                Err(err) => return Err(err),
+            };
+            merge(base, ours, theirs, context, bank.parse.mode(), ours, &BackupPolicy::disabled())
+        }
+        #[cfg(target_os = "linux")]
+        Command::Watch { dir, debounce, parse } => {
+            let mut watcher = BankWatcher::new(dir, parse.mode(), Duration::from_millis(*debounce), backup)?;
+            info!("Watching {} for bank changes...", dir.display());
+            watcher.run()
+        }
+        Command::Inventory { root, parse } => inventory(root, parse.mode()),
+        Command::Transfer { bank, to_player, to_author, to_account_root, all_players: _, move_bank, force } => {
+            let target = TransferTarget {
+                player: *to_player,
+                author: *to_author,
+                account_root: to_account_root.as_deref(),
+            };
+            transfer(bank, target, *move_bank, *force, &backup)
+        }
+        Command::Backups { command: BackupsCommand::List { bank } } => list_backups(bank, &backup),
+        Command::Recover { bank, authors, players, bank_names, parse } => {
+            let candidates = Candidates {
+                authors: authors.clone(),
+                players: players.clone(),
+                bank_names: bank_names.clone(),
+            };
+            recover_context(bank, candidates, parse.mode())
+        }
+        Command::Restore { bank, at } => {
+            let restored = backup.restore(bank, at.as_deref())?;
+            info!("Restored {} from the backup of {}", bank.display(), restored.timestamp);
+            Ok(())
+        }
+        Command::Textconv { path } => textconv(path),
+        Command::Explain { bank, format } => explain(bank, *format),
+        #[cfg(feature = "serde")]
+        Command::Export { bank, format, output } => export(bank, *format, output.as_deref()),
+        #[cfg(feature = "serde")]
+        Command::Import { json_path, bank, force } => import(json_path, bank, *force, &backup),
+        #[cfg(feature = "serde")]
+        Command::ApplyPatch { patch, banks, author_handle, player_handle, parse, edit } => {
+            let banks: Vec<BankArgs> = banks
+                .iter()
+                .map(|bank_path| BankArgs {
+                    bank_path: bank_path.clone(),
+                    bank_name: None,
+                    author_handle: *author_handle,
+                    player_handle: *player_handle,
+                    parse: parse.clone(),
+                })
+                .collect();
+            apply_patch(patch, &banks, edit, &backup)
+        }
+        #[cfg(feature = "serde")]
+        Command::Validate { schema, banks, parse } => validate(schema, banks, parse.mode()),
+        Command::Set { bank, key, value, edit } => edit_bank(bank, edit, &backup, |bank_parser| {
+            bank_parser.set_value(&key.section, &key.key, value.clone())
+        }),
+        Command::Delete { bank, key, edit } => edit_bank(bank, edit, &backup, |bank_parser| {
+            bank_parser.remove_key(&key.section, &key.key)
+        }),
+        Command::RenameKey { bank, key, new_name, edit } => edit_bank(bank, edit, &backup, |bank_parser| {
+            bank_parser.rename_key(&key.section, &key.key, new_name)
+        }),
+        Command::RenameSection { bank, section, new_name, edit } => edit_bank(bank, edit, &backup, |bank_parser| {
+            bank_parser.rename_section(section, new_name)
+        }),
+    }
+    .map(|()| EXIT_SUCCESS)
+}
+
 fn main(){
     match run_app() {
-        Ok(_) => {
-            info!("Operation completed successfully.");
+        Ok(code) => {
+            // A mismatch or missing signature is an outcome, but not a success.
+            if code == EXIT_SUCCESS {
+                info!("Operation completed successfully.");
+            }
+            std::process::exit(code);
         }
         Err(err) => {
 
             error!("{}", err);
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
         }
     }
 }
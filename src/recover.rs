@@ -0,0 +1,174 @@
+use crate::bank_parser::BankParser;
+use crate::handle::{AuthorHandle, Handle, HandleRange, PlayerHandle};
+use crate::signing_context::SigningContext;
+use rayon::prelude::*;
+use sha1::{Digest, Sha1};
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+pub enum RecoverError {
+    #[error("The bank has no signature to recover its signing context from")]
+    MissingSignature,
+    #[error("The bank's signature '{0}' is not a SHA-1 hash")]
+    InvalidSignature(String),
+    #[error("None of the {0} candidate signing context(s) produces the bank's signature")]
+    NoMatch(u128),
+}
+
+/// The signing contexts to try: every combination of an author, a player and a bank name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidates {
+    pub authors: Vec<HandleRange>,
+    pub players: Vec<HandleRange>,
+    pub bank_names: Vec<String>,
+}
+
+impl Candidates {
+    /// The number of contexts to try, `u128::MAX` if there are more.
+    pub fn count(&self) -> u128 {
+        let handles =
+            |ranges: &[HandleRange]| ranges.iter().fold(0u128, |sum, range| sum.saturating_add(range.count()));
+        handles(&self.authors).saturating_mul(handles(&self.players)).saturating_mul(self.bank_names.len() as u128)
+    }
+}
+
+/// Finds the contexts among `candidates` that `bank`'s stored signature was computed
+/// with, sorted by author, player and bank name.
+///
+/// Handles and bank name lead the payload, so they are what varies between candidates,
+/// while the bank content that follows them is the same for all. SHA-1 hashes from the
+/// front, so the content cannot be hashed once up front; instead its payload bytes are
+/// built once, and the hash state after each author, and after each author and player,
+/// is computed once and cloned for the items that follow. Authors and players are
+/// searched in parallel.
+///
+/// More than one context can match: the payload items are not delimited, so e.g. the
+/// player `2-S2-1-12` with the bank `3Bank` and the player `2-S2-1-123` with the bank
+/// `Bank` hash the same.
+pub fn recover(bank: &BankParser, candidates: &Candidates) -> Result<Vec<SigningContext>, RecoverError> {
+    let stored = bank.current_signature.as_deref().ok_or(RecoverError::MissingSignature)?;
+    let target = hex::decode(stored)
+        .ok()
+        .filter(|digest| digest.len() == Sha1::output_size())
+        .ok_or_else(|| RecoverError::InvalidSignature(stored.to_string()))?;
+    let target = target.as_slice();
+    let content = bank.payload().content_bytes();
+    let content = content.as_slice();
+    let bank_names = candidates.bank_names.as_slice();
+
+    let mut found: Vec<SigningContext> = handles(&candidates.authors)
+        .flat_map(|author| {
+            let mut author_state = Sha1::new();
+            author_state.update(author.to_string());
+            handles(&candidates.players).flat_map_iter(move |player| {
+                let mut player_state = author_state.clone();
+                player_state.update(player.to_string());
+                bank_names.iter().filter_map(move |bank_name| {
+                    let mut state = player_state.clone();
+                    state.update(bank_name);
+                    state.update(content);
+                    (state.finalize().as_slice() == target)
+                        .then(|| SigningContext::new(bank_name.clone(), AuthorHandle(author), PlayerHandle(player)))
+                })
+            })
+        })
+        .collect();
+
+    if found.is_empty() {
+        return Err(RecoverError::NoMatch(candidates.count()));
+    }
+    found.sort_by(|a, b| {
+        (a.author_handle, a.player_handle, &a.bank_name).cmp(&(b.author_handle, b.player_handle, &b.bank_name))
+    });
+    Ok(found)
+}
+
+/// Every handle of `ranges`, as a parallel iterator.
+fn handles(ranges: &[HandleRange]) -> impl ParallelIterator<Item = Handle> + '_ {
+    ranges.par_iter().flat_map(|range| {
+        range
+            .ids
+            .clone()
+            .into_par_iter()
+            .map(move |id| Handle::new(range.region, range.realm, id))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Bank version="1">
+    <Section name="Stats">
+        <Key name="Gold"><Value int="7"/></Key>
+        <Key name="Note"><Value text="not signed"/></Key>
+    </Section>
+    <Section name="Options">
+        <Key name="Rate"><Value fixed="1.5"/></Key>
+    </Section>
+</Bank>
+"#;
+
+    /// The bank signed for `context`.
+    fn signed(context: &SigningContext) -> BankParser {
+        let mut bank = BankParser::from_str(SOURCE, context.clone()).unwrap();
+        bank.apply_signature().unwrap();
+        bank
+    }
+
+    fn context(bank_name: &str, author: &str, player: &str) -> SigningContext {
+        SigningContext::new(bank_name, author.parse().unwrap(), player.parse().unwrap())
+    }
+
+    fn candidates(authors: &str, players: &str, bank_names: &[&str]) -> Candidates {
+        Candidates {
+            authors: vec![authors.parse().unwrap()],
+            players: vec![players.parse().unwrap()],
+            bank_names: bank_names.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn recovers_the_signing_context() {
+        let context = context("Progress", "1-S2-1-1005", "2-S2-1-2003");
+        let candidates = candidates("1-S2-1-1000..1009", "2-S2-1-2000..2009", &["Bank", "Progress"]);
+        assert_eq!(candidates.count(), 200);
+        assert_eq!(recover(&signed(&context), &candidates), Ok(vec![context]));
+    }
+
+    #[test]
+    fn reusing_hash_states_matches_the_signature() {
+        // Every candidate signs its own bank, which only that candidate must recover.
+        let candidates = candidates("1-S2-1-7..9", "2-S2-1-98..101", &["A", "Bank"]);
+        for author in candidates.authors[0].handles() {
+            for player in candidates.players[0].handles() {
+                for bank_name in &candidates.bank_names {
+                    let context = SigningContext::new(bank_name.clone(), AuthorHandle(author), PlayerHandle(player));
+                    let bank = signed(&context);
+                    assert_eq!(bank.signature, bank.current_signature.clone().unwrap());
+                    assert_eq!(recover(&bank, &candidates), Ok(vec![context]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_banks_it_cannot_recover() {
+        let bank = signed(&context("Bank", "1-S2-1-1", "2-S2-1-1"));
+        let candidates = candidates("1-S2-1-2..3", "2-S2-1-1", &["Bank"]);
+        assert_eq!(recover(&bank, &candidates), Err(RecoverError::NoMatch(2)));
+
+        let unsigned = BankParser::from_str(SOURCE, SigningContext::unsigned("Bank")).unwrap();
+        assert_eq!(recover(&unsigned, &candidates), Err(RecoverError::MissingSignature));
+        let source = SOURCE.replace("</Bank>", r#"<Signature value="XYZ"/></Bank>"#);
+        let invalid = BankParser::from_str(&source, SigningContext::unsigned("Bank")).unwrap();
+        assert_eq!(recover(&invalid, &candidates), Err(RecoverError::InvalidSignature("XYZ".to_string())));
+    }
+
+    #[test]
+    fn counts_candidates_without_overflowing() {
+        let every = format!("1-S2-1-0..{}", u64::MAX);
+        assert_eq!(candidates(&every, &every, &["A", "B"]).count(), u128::MAX);
+        assert_eq!(candidates(&every, "2-S2-1-1", &["A"]).count(), u128::from(u64::MAX) + 1);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::handle::{AuthorHandle, Handle, PlayerHandle, Region};
+
+/// The metadata that, together with the bank content, goes into a bank signature.
+///
+/// The game derives these values from where the bank lives on disk; library users
+/// that hold a bank in memory provide them directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SigningContext {
+    pub bank_name: String,
+    pub author_handle: AuthorHandle,
+    pub player_handle: PlayerHandle,
+}
+
+impl SigningContext {
+    pub fn new(bank_name: impl Into<String>, author_handle: AuthorHandle, player_handle: PlayerHandle) -> Self {
+        SigningContext {
+            bank_name: bank_name.into(),
+            author_handle,
+            player_handle,
+        }
+    }
+
+    /// A context for reading a bank whose signature does not matter, e.g. to compare
+    /// its content. The handles are placeholders, so signatures computed with it do not
+    /// hold for any account.
+    pub fn unsigned(bank_name: impl Into<String>) -> Self {
+        let placeholder = Handle::new(Region::Us, 0, 0);
+        Self::new(bank_name, AuthorHandle(placeholder), PlayerHandle(placeholder))
+    }
+}
@@ -0,0 +1,330 @@
+use crate::backup::{BackupPolicy, DEFAULT_BACKUP_DIR};
+use crate::bank_file::content_hash;
+use crate::bank_parser::diff::BankDiff;
+use crate::bank_parser::section::Section;
+use crate::bank_parser::{BankParser, ParseMode};
+use crate::bank_path::BankPath;
+use crate::batch::is_bank_file;
+use crate::{AppResult, BankArgs, ParseArgs};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use log::{error, info};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fs, io, thread};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Re-signs the banks under a directory whenever they change, using inotify.
+///
+/// Changes are debounced per bank: a bank is handled once no event has arrived for it
+/// for the debounce period, so a save written in several steps is re-signed once. A
+/// bank whose content is exactly what the watcher last wrote to it is skipped, so the
+/// watcher's own writes do not trigger it again.
+pub struct BankWatcher {
+    inotify: Inotify,
+    directories: HashMap<WatchDescriptor, PathBuf>,
+    pending: Debounce,
+    resigner: Resigner,
+}
+
+/// Banks changed since they were last handled, with the time of their last event.
+#[derive(Debug)]
+struct Debounce {
+    period: Duration,
+    pending: HashMap<PathBuf, Instant>,
+}
+
+/// Re-signs changed banks, leaving alone those it wrote itself.
+#[derive(Debug)]
+struct Resigner {
+    mode: ParseMode,
+    backup: BackupPolicy,
+    /// What the watcher last wrote to each bank.
+    written: HashMap<PathBuf, String>,
+    /// Sections of each bank as last seen, to describe what changed.
+    known: HashMap<PathBuf, Vec<Section>>,
+}
+
+impl BankWatcher {
+    /// Watches `root` and every directory below it. Banks are backed up according to
+    /// `backup` before they are re-signed.
+    pub fn new(root: impl AsRef<Path>, mode: ParseMode, debounce: Duration, backup: BackupPolicy) -> AppResult<Self> {
+        let mut watcher = BankWatcher {
+            inotify: Inotify::init()?,
+            directories: HashMap::new(),
+            pending: Debounce::new(debounce),
+            resigner: Resigner::new(mode, backup),
+        };
+        watcher.watch_tree(root.as_ref())?;
+        Ok(watcher)
+    }
+
+    /// Handles changes until an error occurs reading events. Problems with single banks
+    /// are logged and do not stop the watcher.
+    pub fn run(&mut self) -> AppResult<()> {
+        let mut buffer = [0; 4096];
+        loop {
+            self.read_events(&mut buffer)?;
+
+            for path in self.pending.take_ready(Instant::now()) {
+                if let Err(err) = self.resigner.handle(&path) {
+                    error!("{}: {}", path.display(), err);
+                }
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Watches `directory` and its subdirectories, and remembers the banks in them.
+    fn watch_tree(&mut self, directory: &Path) -> AppResult<()> {
+        let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE;
+        let descriptor = self.inotify.watches().add(directory, mask)?;
+        self.directories.insert(descriptor, directory.to_path_buf());
+
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                // Backups are not banks, and change with every re-sign.
+                if path.file_name() != Some(DEFAULT_BACKUP_DIR.as_ref()) {
+                    self.watch_tree(&path)?;
+                }
+            } else if is_bank_file(&path) {
+                self.resigner.remember(&path);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_events(&mut self, buffer: &mut [u8]) -> AppResult<()> {
+        let events = match self.inotify.read_events(buffer) {
+            Ok(events) => events,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut new_directories = Vec::new();
+        for event in events {
+            let (Some(directory), Some(name)) = (self.directories.get(&event.wd), event.name) else {
+                continue;
+            };
+            let path = directory.join(name);
+            if event.mask.contains(EventMask::ISDIR) {
+                if name != DEFAULT_BACKUP_DIR {
+                    new_directories.push(path);
+                }
+            } else if event.mask.intersects(EventMask::CLOSE_WRITE | EventMask::MOVED_TO) && is_bank_file(&path) {
+                self.pending.touch(path, Instant::now());
+            }
+        }
+        for directory in new_directories {
+            self.watch_tree(&directory)?;
+        }
+        Ok(())
+    }
+
+}
+
+impl Debounce {
+    fn new(period: Duration) -> Self {
+        Debounce { period, pending: HashMap::new() }
+    }
+
+    /// Notes an event for `path` at `now`, restarting its debounce period.
+    fn touch(&mut self, path: PathBuf, now: Instant) {
+        self.pending.insert(path, now);
+    }
+
+    /// Removes and returns the banks without events for the debounce period, by path.
+    fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, last_event)| now.duration_since(**last_event) >= self.period)
+            .map(|(path, _)| path.clone())
+            .collect();
+        ready.sort();
+        for path in &ready {
+            self.pending.remove(path);
+        }
+        ready
+    }
+}
+
+impl Resigner {
+    fn new(mode: ParseMode, backup: BackupPolicy) -> Self {
+        Resigner { mode, backup, written: HashMap::new(), known: HashMap::new() }
+    }
+
+    /// Keeps the sections of the bank at `path` to describe its next change, if it reads.
+    fn remember(&mut self, path: &Path) {
+        if let Ok(bank) = self.load(path) {
+            self.known.insert(path.to_path_buf(), bank.sections);
+        }
+    }
+
+    fn handle(&mut self, path: &Path) -> AppResult<()> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            // Replaced or removed again before the debounce ran out.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        if self.written.get(path) == Some(&content) {
+            return Ok(());
+        }
+
+        let bank = self.parse(path, &content)?;
+        let changes = match self.known.get(path) {
+            Some(sections) => describe(&BankDiff::new(sections, &bank.sections)),
+            None => "new bank".to_string(),
+        };
+        self.known.insert(path.to_path_buf(), bank.sections.clone());
+
+        if bank.current_signature.as_deref() == Some(bank.signature.as_str()) {
+            info!("{}: {}; signature matches", path.display(), changes);
+            return Ok(());
+        }
+        bank.replace_signature()?;
+        self.written.insert(path.to_path_buf(), fs::read_to_string(path)?);
+        info!("{}: {}; re-signed {}", path.display(), changes, bank.signature);
+        Ok(())
+    }
+
+    fn load(&self, path: &Path) -> AppResult<BankParser> {
+        let content = fs::read_to_string(path)?;
+        self.parse(path, &content)
+    }
+
+    fn parse(&self, path: &Path, content: &str) -> AppResult<BankParser> {
+        let bank_path = BankPath::new(&BankArgs {
+            bank_path: path.display().to_string(),
+            bank_name: None,
+            author_handle: None,
+            player_handle: None,
+            parse: ParseArgs { lenient: self.mode == ParseMode::Lenient },
+        })?;
+        let mut bank = BankParser::from_reader_with_mode(content.as_bytes(), bank_path.context(), self.mode)?;
+        bank.source_path = Some(path.to_path_buf());
+        bank.source_hash = Some(content_hash(content.as_bytes()));
+        bank.backup = self.backup.clone();
+        Ok(bank)
+    }
+}
+
+/// The changes on one line.
+fn describe(diff: &BankDiff) -> String {
+    if diff.is_empty() {
+        return "no changes".to_string();
+    }
+    diff.changes
+        .iter()
+        .map(|change| change.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = concat!(
+        r#"<Bank version="1"><Section name="Stats"><Key name="Gold"><Value int="7"/></Key></Section>"#,
+        r#"<Signature value="ABC"/></Bank>"#,
+    );
+
+    /// A scratch `Accounts` tree holding one bank, removed when dropped.
+    struct Accounts {
+        root: PathBuf,
+        bank: PathBuf,
+    }
+
+    impl Accounts {
+        fn new(test: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("sc2_bank_signer-watch-{}-{}", std::process::id(), test));
+            let _ = fs::remove_dir_all(&root);
+            let dir = root.join("Accounts/1/2-S2-1-7654321/Banks/1-S2-1-1234567");
+            fs::create_dir_all(&dir).unwrap();
+            let bank = dir.join("Bank.SC2Bank");
+            fs::write(&bank, CONTENT).unwrap();
+            Accounts { root, bank }
+        }
+
+        fn content(&self) -> String {
+            fs::read_to_string(&self.bank).unwrap()
+        }
+    }
+
+    impl Drop for Accounts {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn waits_for_the_debounce_period_after_the_last_event() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut debounce = Debounce::new(Duration::from_millis(500));
+        debounce.touch(PathBuf::from("B.SC2Bank"), at(0));
+        debounce.touch(PathBuf::from("A.SC2Bank"), at(100));
+        // Another event for B starts its period over.
+        debounce.touch(PathBuf::from("B.SC2Bank"), at(400));
+        assert!(debounce.take_ready(at(499)).is_empty());
+        assert_eq!(debounce.take_ready(at(600)), [PathBuf::from("A.SC2Bank")]);
+        assert!(debounce.take_ready(at(899)).is_empty());
+        assert_eq!(debounce.take_ready(at(900)), [PathBuf::from("B.SC2Bank")]);
+        assert!(debounce.take_ready(at(10_000)).is_empty());
+
+        debounce.touch(PathBuf::from("B.SC2Bank"), at(1000));
+        debounce.touch(PathBuf::from("A.SC2Bank"), at(1000));
+        assert_eq!(debounce.take_ready(at(1500)), [PathBuf::from("A.SC2Bank"), PathBuf::from("B.SC2Bank")]);
+    }
+
+    #[test]
+    fn re_signs_changed_banks() {
+        let accounts = Accounts::new("resign");
+        let mut resigner = Resigner::new(ParseMode::Strict, BackupPolicy::disabled());
+        resigner.remember(&accounts.bank);
+        resigner.handle(&accounts.bank).unwrap();
+        let signed = accounts.content();
+        assert_ne!(signed, CONTENT);
+        assert_eq!(resigner.written.get(&accounts.bank), Some(&signed));
+
+        // A bank the game saves again is re-signed again.
+        fs::write(&accounts.bank, signed.replace(r#"int="7""#, r#"int="8""#)).unwrap();
+        resigner.handle(&accounts.bank).unwrap();
+        let resigned = accounts.content();
+        assert!(resigned.contains(r#"int="8""#));
+        assert_ne!(resigned, signed.replace(r#"int="7""#, r#"int="8""#));
+        assert_eq!(resigner.written.get(&accounts.bank), Some(&resigned));
+    }
+
+    #[test]
+    fn skips_its_own_writes() {
+        let accounts = Accounts::new("own-writes");
+        let mut resigner = Resigner::new(ParseMode::Strict, BackupPolicy::disabled());
+        resigner.handle(&accounts.bank).unwrap();
+        // The event of its own write finds what it wrote, and does not even read the bank.
+        resigner.known.clear();
+        resigner.handle(&accounts.bank).unwrap();
+        assert!(resigner.known.is_empty());
+
+        // The same content written by someone else after a change is handled.
+        let written = accounts.content();
+        fs::write(&accounts.bank, CONTENT).unwrap();
+        resigner.handle(&accounts.bank).unwrap();
+        assert_eq!(accounts.content(), written);
+        assert!(resigner.known.contains_key(&accounts.bank));
+    }
+
+    #[test]
+    fn ignores_banks_removed_before_they_are_handled() {
+        let accounts = Accounts::new("removed");
+        let mut resigner = Resigner::new(ParseMode::Strict, BackupPolicy::disabled());
+        fs::remove_file(&accounts.bank).unwrap();
+        resigner.handle(&accounts.bank).unwrap();
+        assert!(resigner.written.is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+use sc2_bank_signer::bank_parser::BankParser;
+use sc2_bank_signer::signing_context::SigningContext;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const AUTHOR: &str = "1-S2-1-1234567";
+const PLAYER: &str = "2-S2-1-7654321";
+
+const CONTENT: &str = r#"<Section name="Stats"><Key name="Gold"><Value int="7"/></Key></Section>"#;
+
+/// A scratch `Accounts` tree, removed when dropped.
+struct Accounts {
+    root: PathBuf,
+}
+
+impl Accounts {
+    fn new(test: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("sc2_bank_signer-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("Accounts/1").join(PLAYER).join("Banks").join(AUTHOR)).unwrap();
+        Accounts { root }
+    }
+
+    fn bank(&self, name: &str) -> PathBuf {
+        self.root
+            .join("Accounts/1")
+            .join(PLAYER)
+            .join("Banks")
+            .join(AUTHOR)
+            .join(format!("{}.SC2Bank", name))
+    }
+
+    /// Writes a bank whose `<Signature>` is `signature`, or the correct one for
+    /// `Some("")`, or none at all for `None`.
+    fn write(&self, name: &str, signature: Option<&str>) -> PathBuf {
+        let path = self.bank(name);
+        let signature = match signature {
+            Some("") => {
+                let context = SigningContext::new(name, AUTHOR.parse().unwrap(), PLAYER.parse().unwrap());
+                let unsigned = format!("<Bank version=\"1\">{}</Bank>", CONTENT);
+                Some(BankParser::from_str(&unsigned, context).unwrap().signature)
+            }
+            other => other.map(str::to_string),
+        };
+        let signature = signature.map(|value| format!("<Signature value=\"{}\"/>", value)).unwrap_or_default();
+        fs::write(&path, format!("<Bank version=\"1\">{}{}</Bank>", CONTENT, signature)).unwrap();
+        path
+    }
+}
+
+impl Drop for Accounts {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn run(args: &[&str], bank: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sc2_bank_signer"))
+        .args(args)
+        .arg(bank)
+        .output()
+        .unwrap()
+}
+
+fn code(args: &[&str], bank: &Path) -> Option<i32> {
+    run(args, bank).status.code()
+}
+
+#[test]
+fn matching_signature_exits_0() {
+    let accounts = Accounts::new("match");
+    assert_eq!(code(&[], &accounts.write("Bank", Some(""))), Some(0));
+}
+
+#[test]
+fn mismatched_signature_exits_1() {
+    let accounts = Accounts::new("mismatch");
+    assert_eq!(code(&[], &accounts.write("Bank", Some("ABC"))), Some(1));
+}
+
+#[test]
+fn invalid_command_line_exits_2() {
+    let accounts = Accounts::new("usage");
+    assert_eq!(code(&["--bogus"], &accounts.write("Bank", Some(""))), Some(2));
+}
+
+#[test]
+fn unreadable_bank_exits_3() {
+    let accounts = Accounts::new("error");
+    let bank = accounts.bank("Broken");
+    fs::write(&bank, "<Bank><Section").unwrap();
+    assert_eq!(code(&[], &bank), Some(3));
+    assert_eq!(code(&[], &accounts.bank("Missing")), Some(3));
+}
+
+#[test]
+fn lenient_reads_unparsable_values() {
+    let accounts = Accounts::new("lenient");
+    let bank = accounts.bank("Bank");
+    fs::write(&bank, r#"<Bank version="1"><Section name="A"><Key name="K"><Value int="x"/></Key></Section></Bank>"#)
+        .unwrap();
+    assert_eq!(code(&[], &bank), Some(3));
+    assert_eq!(code(&["--lenient"], &bank), Some(4));
+}
+
+#[test]
+fn missing_signature_exits_4() {
+    let accounts = Accounts::new("missing");
+    assert_eq!(code(&[], &accounts.write("Bank", None)), Some(4));
+}
+
+#[test]
+fn written_signature_exits_0() {
+    let accounts = Accounts::new("write");
+    let mismatched = accounts.write("Mismatched", Some("ABC"));
+    let unsigned = accounts.write("Unsigned", None);
+    assert_eq!(code(&["--write", "--no-backup"], &mismatched), Some(0));
+    assert_eq!(code(&["--write", "--no-backup"], &unsigned), Some(0));
+    assert_eq!(code(&[], &mismatched), Some(0));
+    assert_eq!(code(&[], &unsigned), Some(0));
+}
+
+#[test]
+fn batch_exits_with_the_worst_outcome() {
+    let accounts = Accounts::new("batch");
+    let dir = accounts.bank("Bank").parent().unwrap().to_path_buf();
+    accounts.write("Matching", Some(""));
+    assert_eq!(code(&[], &dir), Some(0));
+    accounts.write("Mismatched", Some("ABC"));
+    assert_eq!(code(&[], &dir), Some(1));
+    accounts.write("Unsigned", None);
+    assert_eq!(code(&[], &dir), Some(4));
+    fs::write(accounts.bank("Broken"), "<Bank><Section").unwrap();
+    assert_eq!(code(&[], &dir), Some(3));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_output_keeps_the_exit_code() {
+    let accounts = Accounts::new("json");
+    let output = run(&["--output", "json"], &accounts.write("Bank", None));
+    assert_eq!(output.status.code(), Some(4));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let report: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(report["status"], "missing-signature");
+    assert_eq!(report["context"]["author_handle"], AUTHOR);
+    assert_eq!(report["file_signature"], serde_json::Value::Null);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn failed_patch_writes_no_bank() {
+    let accounts = Accounts::new("patch");
+    let first = accounts.write("First", Some(""));
+    let second = accounts.bank("Second");
+    fs::write(&second, format!("<Bank version=\"1\">{}</Bank>", CONTENT.replace("\"7\"", "\"8\""))).unwrap();
+    let patch = accounts.root.join("patch.json");
+    fs::write(&patch, r#"{"operations": [{"op": "set", "key": "Stats/Gold", "value": "int=9", "expect": "int=7"}]}"#)
+        .unwrap();
+    let (first_before, second_before) = (fs::read(&first).unwrap(), fs::read(&second).unwrap());
+    let patch_code = |banks: &[&Path]| {
+        Command::new(env!("CARGO_BIN_EXE_sc2_bank_signer"))
+            .args(["apply-patch", "--no-backup"])
+            .arg(&patch)
+            .args(banks)
+            .status()
+            .unwrap()
+            .code()
+    };
+    assert_eq!(patch_code(&[&first, &second]), Some(3));
+    assert_eq!(fs::read(&first).unwrap(), first_before);
+    assert_eq!(fs::read(&second).unwrap(), second_before);
+    assert_eq!(patch_code(&[&first]), Some(0));
+    assert_ne!(fs::read(&first).unwrap(), first_before);
+    assert_eq!(code(&[], &first), Some(0));
+}
+
+#[test]
+fn merge_driver_needs_handles_for_a_repository_path() {
+    let accounts = Accounts::new("merge-driver");
+    let ours = accounts.write("Bank", Some(""));
+    let base = accounts.root.join("base");
+    let theirs = accounts.root.join("theirs");
+    fs::copy(&ours, &base).unwrap();
+    fs::write(&theirs, fs::read_to_string(&ours).unwrap().replace("int=\"7\"", "int=\"8\"")).unwrap();
+    let merge_driver = |handles: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_sc2_bank_signer"))
+            .arg("merge-driver")
+            .args([&base, &ours, &theirs])
+            .arg("refs/Bank.SC2Bank")
+            .args(handles)
+            .output()
+            .unwrap()
+    };
+
+    let output = merge_driver(&[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("give it with -n, -a and -p"));
+    assert_eq!(fs::read_to_string(&base).unwrap(), fs::read_to_string(&ours).unwrap());
+
+    assert_eq!(merge_driver(&["-a", AUTHOR, "-p", PLAYER]).status.code(), Some(0));
+    assert!(fs::read_to_string(&ours).unwrap().contains("int=\"8\""));
+    assert_eq!(code(&[], &ours), Some(0));
+}
+
+#[test]
+fn reports_success_only_for_exit_0() {
+    let accounts = Accounts::new("success-log");
+    let success = |bank: &Path| String::from_utf8(run(&[], bank).stderr).unwrap().contains("completed successfully");
+    assert!(success(&accounts.write("Matching", Some(""))));
+    assert!(!success(&accounts.write("Mismatched", Some("ABC"))));
+    assert!(!success(&accounts.write("Unsigned", None)));
+}
+
+#[test]
+fn transfer_to_all_players_writes_every_copy_or_none() {
+    let accounts = Accounts::new("transfer-all");
+    let bank = accounts.write("Bank", Some(""));
+    let copy = |player: &str| {
+        let dir = accounts.root.join("Accounts/1").join(player).join("Banks").join(AUTHOR);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("Bank.SC2Bank")
+    };
+    let (second, third) = (copy("2-S2-1-1"), copy("2-S2-1-2"));
+    // A directory in the way of the temporary file makes writing the third copy fail.
+    let blocked = third.with_file_name(".Bank.SC2Bank.tmp");
+    fs::create_dir(&blocked).unwrap();
+    let transfer = || code(&["transfer", "--all-players", "--no-backup"], &bank);
+    assert_eq!(transfer(), Some(3));
+    assert!(!second.exists());
+    assert!(!third.exists());
+
+    fs::remove_dir(&blocked).unwrap();
+    assert_eq!(transfer(), Some(0));
+    assert_eq!(code(&[], &second), Some(0));
+    assert_eq!(code(&[], &third), Some(0));
+}